@@ -76,6 +76,11 @@ pub(crate) fn validate_habit_logic(
         return false;
     }
 
+    // A rename keeps the session count and only changes habit_name
+    if output.total_sessions == input.total_sessions && output.habit_name != input.habit_name {
+        return validate_rename(&input, &output);
+    }
+
     // Rule 2: Sessions must increment by exactly 1
     if output.total_sessions != input.total_sessions + 1 {
         eprintln!(
@@ -97,7 +102,16 @@ pub(crate) fn validate_habit_logic(
         }
     }
 
-    // Rule 4: Validate badges are correct for session count
+    // Rule 4: The name only changes through a rename
+    if output.habit_name != input.habit_name {
+        eprintln!(
+            "✗ habit_name can't change in a session update ('{}' → '{}')",
+            input.habit_name, output.habit_name
+        );
+        return false;
+    }
+
+    // Rule 5: Validate badges are correct for session count
     let expected_badges = get_badges_for_sessions(output.total_sessions);
     if output.badges != expected_badges {
         eprintln!(
@@ -114,6 +128,46 @@ pub(crate) fn validate_habit_logic(
     true
 }
 
+// Rename: sessions, badges, creation time and the update clock carry over
+// unchanged, the new name must be non-empty
+fn validate_rename(input: &HabitContent, output: &HabitContent) -> bool {
+    if output.habit_name.trim().is_empty() {
+        eprintln!("✗ Renamed habit_name must not be empty");
+        return false;
+    }
+
+    if output.created_at != input.created_at {
+        eprintln!(
+            "✗ created_at can't change in a rename (was: {:?}, now: {:?})",
+            input.created_at, output.created_at
+        );
+        return false;
+    }
+
+    // Otherwise a rename could clear last_updated and skip the update interval
+    if output.last_updated != input.last_updated {
+        eprintln!(
+            "✗ last_updated can't change in a rename (was: {:?}, now: {:?})",
+            input.last_updated, output.last_updated
+        );
+        return false;
+    }
+
+    if output.badges != input.badges {
+        eprintln!(
+            "✗ Badges can't change in a rename. Expected: {:?}, Got: {:?}",
+            input.badges, output.badges
+        );
+        return false;
+    }
+
+    eprintln!(
+        "✓ Rename validated: '{}' → '{}' at {} sessions",
+        input.habit_name, output.habit_name, output.total_sessions
+    );
+    true
+}
+
 // Badge system - The Samurai Path to Mastery (66 Days)
 // Based on neuroscience (Robin Sharma) + Bushido philosophy
 fn get_badges_for_sessions(sessions: u64) -> Vec<String> {
@@ -351,6 +405,26 @@ mod test {
         assert!(content.last_updated.is_some());
     }
 
+    #[test]
+    fn test_ignores_unknown_fields() {
+        // The client adds a per-update nonce; it must not affect parsing
+        let json = r#"{
+            "name": "🗡️ Habit Tracker",
+            "description": "Tracking habit: Meditation",
+            "owner": "user123",
+            "habit_name": "Meditation",
+            "total_sessions": 1,
+            "last_updated": 1000000,
+            "nonce": 1700000000123456789,
+            "badges": ["🌸 First Blood"]
+        }"#;
+
+        let content: HabitContent = serde_json::from_str(json).unwrap();
+
+        assert_eq!(content.total_sessions, 1);
+        assert_eq!(content.badges, get_badges_for_sessions(1));
+    }
+
     #[test]
     fn test_min_update_interval_constant() {
         // Verify the constant is set for testing (5 seconds)
@@ -651,4 +725,108 @@ mod test {
         assert!(!result, "Should REJECT when sessions don't increment");
         println!("✓ Correctly rejected no change in sessions");
     }
+
+    #[test]
+    fn test_meta_is_free_form() {
+        // Integrator metadata rides along under `meta`; any shape (or a
+        // changed value) must not affect parsing or validity
+        let input: HabitContent = serde_json::from_str(r#"{
+            "name": "🗡️ Habit Tracker",
+            "description": "Tracking habit: Meditation",
+            "owner": "user123",
+            "habit_name": "Meditation",
+            "total_sessions": 0,
+            "created_at": 1000000,
+            "meta": {"user_id": "42", "ref": "abc"},
+            "badges": []
+        }"#).unwrap();
+
+        let output: HabitContent = serde_json::from_str(r#"{
+            "name": "🗡️ Habit Tracker",
+            "description": "Tracking habit: Meditation",
+            "owner": "user123",
+            "habit_name": "Meditation",
+            "total_sessions": 1,
+            "last_updated": 1000100,
+            "meta": {"nested": {"anything": [1, 2, 3]}},
+            "badges": ["🌸 First Blood"]
+        }"#).unwrap();
+
+        assert!(validate_habit_logic(None, input.clone()), "creation with meta");
+        assert!(
+            validate_habit_logic(Some(input), output),
+            "meta must not gate updates"
+        );
+    }
+
+    #[test]
+    fn test_rename_keeps_sessions() {
+        // TEST: A rename changes habit_name only; sessions and badges stay put
+
+        let base_time = 1000000i64;
+
+        let input = HabitContent {
+            name: "Test Habit".to_string(),
+            description: "Test".to_string(),
+            owner: "user123".to_string(),
+            habit_name: "Meditation".to_string(),
+            total_sessions: 5,
+            created_at: Some(base_time - 10000),
+            last_updated: Some(base_time),
+            badges: get_badges_for_sessions(5),
+        };
+
+        let renamed = HabitContent {
+            habit_name: "Morning Meditation".to_string(),
+            description: "Tracking habit: Morning Meditation".to_string(),
+            ..input.clone()
+        };
+        assert!(
+            validate_habit_logic(Some(input.clone()), renamed.clone()),
+            "Should ACCEPT a rename"
+        );
+
+        let blank = HabitContent {
+            habit_name: "  ".to_string(),
+            ..renamed.clone()
+        };
+        assert!(!validate_habit_logic(Some(input.clone()), blank), "Should REJECT a blank name");
+
+        let stolen = HabitContent {
+            owner: "attacker".to_string(),
+            ..renamed.clone()
+        };
+        assert!(!validate_habit_logic(Some(input.clone()), stolen), "Should REJECT an owner change");
+
+        let extra_badges = HabitContent {
+            badges: get_badges_for_sessions(100),
+            ..renamed
+        };
+        assert!(!validate_habit_logic(Some(input.clone()), extra_badges), "Should REJECT badge changes");
+
+        let clock_reset = HabitContent {
+            habit_name: "Morning Meditation".to_string(),
+            last_updated: None,
+            ..input.clone()
+        };
+        assert!(!validate_habit_logic(Some(input.clone()), clock_reset), "Should REJECT a clock reset");
+
+        let backdated = HabitContent {
+            habit_name: "Morning Meditation".to_string(),
+            created_at: Some(base_time - 999_999),
+            ..input.clone()
+        };
+        assert!(!validate_habit_logic(Some(input.clone()), backdated), "Should REJECT a created_at change");
+
+        // Renaming and incrementing at once isn't either transition
+        let both = HabitContent {
+            habit_name: "Morning Meditation".to_string(),
+            total_sessions: 6,
+            last_updated: Some(base_time + MIN_UPDATE_INTERVAL_SECS),
+            badges: get_badges_for_sessions(6),
+            ..input.clone()
+        };
+        assert!(!validate_habit_logic(Some(input), both), "Should REJECT rename + increment");
+        println!("✓ Rename accepted only with sessions and badges unchanged");
+    }
 }
\ No newline at end of file
@@ -3,9 +3,22 @@
 //! A Bitcoin NFT application for tracking habits with on-chain verification.
 //! Supports both CLI and API server modes.
 //!
-use axum::{extract::Json, http::StatusCode, response::IntoResponse, routing::post, Router};
+use axum::{
+    extract::{ConnectInfo, Json, Path, Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Router,
+};
+use bitcoincore_rpc::bitcoin;
 use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::Arc;
 use tower_http::cors::CorsLayer;
 
 mod nft;
@@ -24,6 +37,44 @@ mod tests;
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// Build and prove for this network instead of the one the node reports.
+    /// The local prover mocks proofs only on regtest
+    #[arg(long, global = true, value_enum)]
+    network: Option<NetworkArg>,
+
+    /// Use --network even if it doesn't match the connected node
+    #[arg(long, global = true, requires = "network")]
+    force_network: bool,
+
+    /// Contract WASM to use instead of HABIT_CONTRACT_WASM / the source tree
+    #[arg(long, global = true)]
+    contract_path: Option<std::path::PathBuf>,
+
+    /// Contract VK to use instead of HABIT_CONTRACT_VK / the source tree
+    #[arg(long, global = true)]
+    vk_path: Option<std::path::PathBuf>,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum NetworkArg {
+    Regtest,
+    Testnet,
+    Testnet4,
+    Signet,
+    Mainnet,
+}
+
+impl From<NetworkArg> for bitcoincore_rpc::bitcoin::Network {
+    fn from(arg: NetworkArg) -> Self {
+        match arg {
+            NetworkArg::Regtest => Self::Regtest,
+            NetworkArg::Testnet => Self::Testnet,
+            NetworkArg::Testnet4 => Self::Testnet4,
+            NetworkArg::Signet => Self::Signet,
+            NetworkArg::Mainnet => Self::Bitcoin,
+        }
+    }
 }
 
 #[derive(Subcommand)]
@@ -32,17 +83,106 @@ enum Commands {
     Create {
         #[arg(long)]
         habit: String,
+        /// Mint the NFT to this address instead of the funding address
+        #[arg(long)]
+        to: Option<String>,
+    },
+    /// Create several habit NFTs, each funded by its own UTXO
+    CreateMany {
+        /// Habit name; repeat for each habit
+        #[arg(long = "habit", required = true)]
+        habits: Vec<String>,
     },
     /// Update NFT (increment session counter)
     Update {
         #[arg(long)]
         utxo: String,
     },
+    /// Change a habit's name, keeping its sessions and badges
+    Rename {
+        #[arg(long)]
+        utxo: String,
+        /// New habit name
+        #[arg(long)]
+        name: String,
+    },
+    /// Build an unsigned create and write it to a file for offline signing
+    CreateUnsigned {
+        #[arg(long)]
+        habit: String,
+        /// Where to write the unsigned transactions (JSON)
+        #[arg(long)]
+        out: std::path::PathBuf,
+    },
+    /// Build an unsigned update and write it to a file for offline signing
+    UpdateUnsigned {
+        #[arg(long)]
+        utxo: String,
+        /// Where to write the unsigned transactions (JSON)
+        #[arg(long)]
+        out: std::path::PathBuf,
+    },
+    /// Broadcast commit and spell transactions signed offline
+    Broadcast {
+        /// File containing the signed commit transaction hex
+        #[arg(long)]
+        commit: std::path::PathBuf,
+        /// File containing the signed spell transaction hex
+        #[arg(long)]
+        spell: std::path::PathBuf,
+    },
     /// View NFT details
     View {
         #[arg(long)]
         utxo: String,
     },
+    /// Total fees paid over an NFT's lifetime
+    Cost {
+        #[arg(long)]
+        utxo: String,
+    },
+    /// Find the current UTXO of an NFT by its app id
+    Locate {
+        #[arg(long)]
+        app_id: String,
+    },
+    /// Sweep all non-NFT wallet UTXOs into a single output
+    Consolidate {
+        /// Fee rate in sat/vB (defaults to HABIT_FEE_RATE or 2.0)
+        #[arg(long)]
+        fee_rate: Option<f64>,
+    },
+    /// Print the app id an identity seed (`{prefix}_{unix_ts}_{funding txid:vout}`)
+    /// maps to, without minting
+    AppId {
+        #[arg(long)]
+        seed: String,
+    },
+    /// Check a signed transaction before broadcasting it
+    Inspect {
+        /// Signed transaction hex
+        #[arg(long)]
+        tx_hex: String,
+    },
+    /// Check the node, wallet, contract, charms binary and funds
+    Doctor,
+    /// Decode and pretty-print the spell of a transaction
+    #[command(group(
+        clap::ArgGroup::new("source")
+            .required(true)
+            .args(["tx_hex", "txid", "file"])
+    ))]
+    ShowSpell {
+        /// Raw transaction hex
+        #[arg(long)]
+        tx_hex: Option<String>,
+        /// Transaction id, fetched via RPC
+        #[arg(long)]
+        txid: Option<String>,
+        /// File containing raw transaction hex
+        #[arg(long)]
+        file: Option<std::path::PathBuf>,
+    },
 }
 
 // ============================================================================
@@ -55,6 +195,23 @@ struct CreateNftRequest {
     address: String,
     funding_utxo: String,
     funding_value: u64,
+    /// Free-form key-values stored under the charm's `meta` object
+    #[serde(default)]
+    metadata: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize)]
+struct FundingInput {
+    utxo: String,
+    value: u64,
+}
+
+#[derive(Deserialize)]
+struct BatchCreateNftRequest {
+    habits: Vec<String>,
+    address: String,
+    // One per habit, used in order
+    funding_utxos: Vec<FundingInput>,
 }
 
 #[derive(Deserialize)]
@@ -69,6 +226,26 @@ struct UpdateNftRequest {
     user_address: String,
     funding_utxo: String,
     funding_value: u64,
+    #[serde(default)]
+    extra_outputs: Vec<ExtraOutput>,
+    // Sessions the client last saw; rejected if the chain has moved on
+    #[serde(default)]
+    expected_sessions: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RenameNftRequest {
+    nft_utxo: String,
+    new_name: String,
+    user_address: String,
+    funding_utxo: String,
+    funding_value: u64,
+}
+
+#[derive(Deserialize)]
+struct ExtraOutput {
+    address: String,
+    sats: u64,
 }
 
 #[derive(Deserialize)]
@@ -76,6 +253,329 @@ struct ViewNftRequest {
     utxo: String,
 }
 
+#[derive(Deserialize)]
+struct BatchViewNftRequest {
+    utxos: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct TxStatusRequest {
+    txid: String,
+}
+
+#[derive(Deserialize)]
+struct InspectTxRequest {
+    signed_hex: String,
+}
+
+#[derive(Deserialize)]
+struct LocateNftRequest {
+    app_id: String,
+}
+
+// ============================================================================
+// Request Validation
+// ============================================================================
+
+/// Largest funding value worth considering: every bitcoin there will be
+const MAX_FUNDING_SATS: u64 = 21_000_000 * 100_000_000;
+
+/// 400 naming the offending field
+fn bad_field(field: &str, msg: impl std::fmt::Display) -> ApiError {
+    ApiError::Status(StatusCode::BAD_REQUEST, format!("{}: {}", field, msg))
+}
+
+fn check_address(field: &str, address: &str) -> Result<(), ApiError> {
+    if address.trim().is_empty() {
+        return Err(bad_field(field, "must not be empty"));
+    }
+    bitcoin::Address::from_str(address)
+        .map(|_| ())
+        .map_err(|e| bad_field(field, format!("not a bitcoin address ({})", e)))
+}
+
+fn check_utxo(field: &str, utxo: &str) -> Result<(), ApiError> {
+    parse_utxo(utxo)
+        .map(|_| ())
+        .map_err(|_| bad_field(field, "expected txid:vout"))
+}
+
+fn check_funding_value(field: &str, sats: u64) -> Result<(), ApiError> {
+    if sats < MIN_FUNDING_SATS {
+        return Err(bad_field(
+            field,
+            format!("must be at least {} sats", MIN_FUNDING_SATS),
+        ));
+    }
+    if sats > MAX_FUNDING_SATS {
+        return Err(bad_field(field, "exceeds the bitcoin supply"));
+    }
+    Ok(())
+}
+
+fn check_hex(field: &str, value: &str) -> Result<(), ApiError> {
+    if value.trim().is_empty() {
+        return Err(bad_field(field, "must not be empty"));
+    }
+    hex::decode(value.trim())
+        .map(|_| ())
+        .map_err(|_| bad_field(field, "not valid hex"))
+}
+
+fn check_habit(field: &str, habit: &str) -> Result<(), ApiError> {
+    if habit.trim().is_empty() {
+        return Err(bad_field(field, "must not be empty"));
+    }
+    Ok(())
+}
+
+impl CreateNftRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        check_habit("habit", &self.habit)?;
+        check_address("address", &self.address)?;
+        check_utxo("funding_utxo", &self.funding_utxo)?;
+        check_funding_value("funding_value", self.funding_value)
+    }
+}
+
+impl BatchCreateNftRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        for (i, habit) in self.habits.iter().enumerate() {
+            check_habit(&format!("habits[{}]", i), habit)?;
+        }
+        check_address("address", &self.address)?;
+        for (i, funding) in self.funding_utxos.iter().enumerate() {
+            check_utxo(&format!("funding_utxos[{}].utxo", i), &funding.utxo)?;
+            check_funding_value(&format!("funding_utxos[{}].value", i), funding.value)?;
+        }
+        Ok(())
+    }
+}
+
+impl BroadcastNftRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        check_hex("signed_commit_hex", &self.signed_commit_hex)?;
+        check_hex("signed_spell_hex", &self.signed_spell_hex)
+    }
+}
+
+impl UpdateNftRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        check_utxo("nft_utxo", &self.nft_utxo)?;
+        check_address("user_address", &self.user_address)?;
+        check_utxo("funding_utxo", &self.funding_utxo)?;
+        check_funding_value("funding_value", self.funding_value)?;
+        for (i, out) in self.extra_outputs.iter().enumerate() {
+            check_address(&format!("extra_outputs[{}].address", i), &out.address)?;
+            let dust = dust_limit(&out.address)
+                .map_err(|e| bad_field(&format!("extra_outputs[{}].address", i), e))?;
+            if out.sats < dust {
+                return Err(bad_field(
+                    &format!("extra_outputs[{}].sats", i),
+                    format!("must be at least {} sats (dust limit)", dust),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RenameNftRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        check_utxo("nft_utxo", &self.nft_utxo)?;
+        validate_habit_name(&self.new_name).map_err(|e| bad_field("new_name", e))?;
+        check_address("user_address", &self.user_address)?;
+        check_utxo("funding_utxo", &self.funding_utxo)?;
+        check_funding_value("funding_value", self.funding_value)
+    }
+}
+
+impl ViewNftRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        check_utxo("utxo", &self.utxo)
+    }
+}
+
+impl TxStatusRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        bitcoin::Txid::from_str(&self.txid)
+            .map(|_| ())
+            .map_err(|_| bad_field("txid", "not a transaction id"))
+    }
+}
+
+impl InspectTxRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        check_hex("signed_hex", &self.signed_hex)
+    }
+}
+
+impl LocateNftRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        if self.app_id.trim().is_empty() {
+            return Err(bad_field("app_id", "must not be empty"));
+        }
+        Ok(())
+    }
+}
+
+/// Maximum number of UTXOs accepted by the batch view endpoint
+const MAX_BATCH_VIEW: usize = 100;
+
+/// Maximum number of habits accepted by the batch create endpoint; each one
+/// runs the prover
+const MAX_BATCH_CREATE: usize = 20;
+
+// Shared server state
+#[derive(Clone)]
+struct AppState {
+    // Read once at startup; the contract files don't change at runtime
+    contract: Arc<ContractInfo>,
+    prover: Arc<dyn Prover>,
+    sessions: Arc<SessionStore>,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+/// Default requests per minute per client IP on the mutating endpoints
+/// (HABIT_RATE_LIMIT_PER_MIN, 0 disables)
+const DEFAULT_RATE_LIMIT_PER_MIN: u32 = 30;
+
+/// Buckets kept before idle (full) ones are dropped
+const RATE_LIMIT_MAX_CLIENTS: usize = 10_000;
+
+/// Per-IP token buckets in front of the endpoints that run the prover or
+/// broadcast. Each bucket holds a minute's worth of requests and refills
+/// continuously.
+struct RateLimiter {
+    per_minute: u32,
+    buckets: std::sync::Mutex<HashMap<std::net::IpAddr, (f64, std::time::Instant)>>,
+}
+
+impl RateLimiter {
+    fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            buckets: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn from_env() -> Self {
+        Self::new(
+            std::env::var("HABIT_RATE_LIMIT_PER_MIN")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_RATE_LIMIT_PER_MIN),
+        )
+    }
+
+    /// Take a token for `ip`, or say how long until the next one
+    fn check(&self, ip: std::net::IpAddr) -> Result<(), std::time::Duration> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+        let capacity = self.per_minute as f64;
+        let per_sec = capacity / 60.0;
+        let now = std::time::Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= RATE_LIMIT_MAX_CLIENTS {
+            buckets.retain(|_, (tokens, last)| {
+                *tokens + now.duration_since(*last).as_secs_f64() * per_sec < capacity
+            });
+        }
+
+        let (tokens, last) = buckets.entry(ip).or_insert((capacity, now));
+        *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * per_sec).min(capacity);
+        *last = now;
+
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(std::time::Duration::from_secs_f64(
+                (1.0 - *tokens) / per_sec,
+            ))
+        }
+    }
+}
+
+/// Default lifetime of a stored unsigned session (HABIT_SESSION_TTL_SECS)
+const DEFAULT_SESSION_TTL_SECS: u64 = 600;
+
+/// Default number of sessions kept at once (HABIT_SESSION_MAX, 0 disables)
+const DEFAULT_SESSION_MAX: usize = 1000;
+
+/// Unsigned responses kept for a while so a wallet that crashed mid-signing
+/// can fetch them again instead of re-proving
+struct SessionStore {
+    ttl: std::time::Duration,
+    max_entries: usize,
+    entries: std::sync::Mutex<HashMap<String, (std::time::Instant, serde_json::Value)>>,
+}
+
+impl SessionStore {
+    fn from_env() -> Self {
+        let ttl_secs = std::env::var("HABIT_SESSION_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SESSION_TTL_SECS);
+        let max_entries = std::env::var("HABIT_SESSION_MAX")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SESSION_MAX);
+
+        Self {
+            ttl: std::time::Duration::from_secs(ttl_secs),
+            max_entries,
+            entries: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Store a response and return its session id, or None when disabled
+    fn insert(&self, value: &impl Serialize) -> Option<String> {
+        if self.max_entries == 0 {
+            return None;
+        }
+        let value = serde_json::to_value(value).ok()?;
+
+        let now = std::time::Instant::now();
+        let mut hasher = Sha256::new();
+        hasher.update(value.to_string().as_bytes());
+        hasher.update(chrono::Utc::now().timestamp_nanos_opt()?.to_le_bytes());
+        let id = hex::encode(&hasher.finalize()[..16]);
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (created, _)| now.duration_since(*created) < self.ttl);
+        // Still full after expiry: drop the oldest
+        while entries.len() >= self.max_entries {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (created, _))| *created)
+                .map(|(id, _)| id.clone())?;
+            entries.remove(&oldest);
+        }
+        entries.insert(id.clone(), (now, value));
+        Some(id)
+    }
+
+    fn get(&self, id: &str) -> Option<serde_json::Value> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(id)
+            .filter(|(created, _)| created.elapsed() < self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+}
+
+/// Unsigned response plus the session id it can be re-fetched with
+#[derive(Serialize)]
+struct WithSession<T> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session_id: Option<String>,
+    #[serde(flatten)]
+    response: T,
+}
+
 // Generic response
 #[derive(Serialize)]
 struct ApiResponse<T> {
@@ -92,37 +592,171 @@ impl<T: Serialize> IntoResponse for ApiResponse<T> {
     }
 }
 
+/// Seconds clients should wait before retrying when the prover is down
+const PROVER_RETRY_AFTER_SECS: u64 = 30;
+
+/// Errors returned by the API handlers
+enum ApiError {
+    /// Plain status + message
+    Status(StatusCode, String),
+    /// The prover couldn't be reached; 503 with a Retry-After hint
+    ProverUnavailable(String),
+    /// The prover ran but rejected the spell; 422
+    ProverRejected(String),
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        match self {
+            ApiError::Status(status, msg) => (status, msg).into_response(),
+            ApiError::ProverUnavailable(msg) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(
+                    axum::http::header::RETRY_AFTER,
+                    PROVER_RETRY_AFTER_SECS.to_string(),
+                )],
+                msg,
+            )
+                .into_response(),
+            ApiError::ProverRejected(msg) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, msg).into_response()
+            }
+        }
+    }
+}
+
+/// Log the full error chain and map it to a 500
+fn internal_error(e: impl std::fmt::Display) -> ApiError {
+    let cause = format!("{:#}", e);
+    log::error!(cause = cause.as_str(); "Request failed");
+    ApiError::Status(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+/// Map an operation error to a response, giving prover failures their own
+/// status so clients know whether retrying makes sense
+fn api_error(e: anyhow::Error) -> ApiError {
+    match e.downcast_ref::<ProverError>() {
+        Some(ProverError::Unavailable(_)) => {
+            log::warn!(cause = format!("{:#}", e).as_str(); "Prover unavailable");
+            ApiError::ProverUnavailable(e.to_string())
+        }
+        Some(ProverError::Rejected(_)) => {
+            log::warn!(cause = format!("{:#}", e).as_str(); "Prover rejected spell");
+            ApiError::ProverRejected(e.to_string())
+        }
+        Some(ProverError::Unauthorized(_)) => {
+            log::error!(cause = format!("{:#}", e).as_str(); "Prover refused credentials");
+            ApiError::Status(StatusCode::BAD_GATEWAY, e.to_string())
+        }
+        None if e.downcast_ref::<SigningError>().is_some() => {
+            log::warn!(cause = format!("{:#}", e).as_str(); "Signing incomplete");
+            ApiError::Status(StatusCode::UNPROCESSABLE_ENTITY, e.to_string())
+        }
+        None => internal_error(e),
+    }
+}
+
 // ============================================================================
 // API Handlers
 // ============================================================================
 
+/// Log the stages of a request's proof as they happen, so a slow prover
+/// shows up in the server log while the client is still waiting
+fn log_progress(operation: &'static str) -> impl FnMut(Progress) {
+    move |stage| log::info!("{}: {}", operation, stage)
+}
+
 async fn handle_create_unsigned(
+    State(state): State<AppState>,
     Json(req): Json<CreateNftRequest>,
-) -> Result<ApiResponse<UnsignedNftResponse>, (StatusCode, String)> {
-    let unsigned = tokio::task::spawn_blocking(move || {
-        create_nft_unsigned(req.habit, req.address, req.funding_utxo, req.funding_value)
-    })
+) -> Result<ApiResponse<WithSession<UnsignedNftResponse>>, ApiError> {
+    req.validate()?;
+
+    let sessions = state.sessions.clone();
+    let unsigned = run_with_progress(
+        move |progress| {
+            create_nft_unsigned(
+                &ReportingProver {
+                    inner: state.prover.as_ref(),
+                    progress,
+                },
+                req.habit,
+                req.address,
+                req.funding_utxo,
+                req.funding_value,
+                CreateOptions {
+                    meta: req.metadata,
+                    ..Default::default()
+                },
+            )
+        },
+        log_progress("create"),
+    )
     .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    .map_err(api_error)?;
 
     Ok(ApiResponse {
         success: true,
         message: Some("Unsigned transactions created".to_string()),
-        data: Some(unsigned),
+        data: Some(WithSession {
+            session_id: sessions.insert(&unsigned),
+            response: unsigned,
+        }),
+    })
+}
+
+async fn handle_create_batch(
+    State(state): State<AppState>,
+    Json(req): Json<BatchCreateNftRequest>,
+) -> Result<ApiResponse<Vec<BatchCreateResult>>, ApiError> {
+    req.validate()?;
+
+    if req.habits.len() > MAX_BATCH_CREATE {
+        return Err(ApiError::Status(
+            StatusCode::BAD_REQUEST,
+            format!("At most {} habits per batch", MAX_BATCH_CREATE),
+        ));
+    }
+
+    let results = tokio::task::spawn_blocking(move || {
+        create_many_unsigned(
+            state.prover.as_ref(),
+            req.habits,
+            &req.address,
+            req.funding_utxos
+                .into_iter()
+                .map(|funding| (funding.utxo, funding.value))
+                .collect(),
+        )
+    })
+    .await
+    .map_err(internal_error)?
+    .map_err(|e| ApiError::Status(StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let succeeded = results.iter().filter(|r| r.unsigned.is_some()).count();
+    Ok(ApiResponse {
+        success: succeeded == results.len(),
+        message: Some(format!(
+            "{} of {} unsigned creates built",
+            succeeded,
+            results.len()
+        )),
+        data: Some(results),
     })
 }
 
 async fn handle_broadcast_nft(
     Json(req): Json<BroadcastNftRequest>,
-) -> Result<ApiResponse<BroadcastNftResponse>, (StatusCode, String)> {
+) -> Result<ApiResponse<BroadcastNftResponse>, ApiError> {
+    req.validate()?;
+
     let result = tokio::task::spawn_blocking(move || {
         let btc = connect_bitcoin()?;
         broadcast_nft(&btc, req.signed_commit_hex, req.signed_spell_hex)
     })
     .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    .map_err(internal_error)?
+    .map_err(api_error)?;
 
     Ok(ApiResponse {
         success: true,
@@ -132,94 +766,539 @@ async fn handle_broadcast_nft(
 }
 
 async fn handle_update_unsigned(
+    State(state): State<AppState>,
     Json(req): Json<UpdateNftRequest>,
-) -> Result<ApiResponse<UnsignedUpdateResponse>, (StatusCode, String)> {
-    let unsigned = tokio::task::spawn_blocking(move || {
-        let btc = connect_bitcoin()?;
-        update_nft_unsigned(
-            &btc, // ← Pass it here
-            req.nft_utxo,
-            req.user_address,
-            req.funding_utxo,
-            req.funding_value,
-        )
-    })
+) -> Result<ApiResponse<WithSession<UnsignedUpdateResponse>>, ApiError> {
+    req.validate()?;
+
+    let sessions = state.sessions.clone();
+    let unsigned = run_with_progress(
+        move |progress| {
+            let btc = connect_bitcoin()?;
+            update_nft_unsigned(
+                &btc,
+                &ReportingProver {
+                    inner: state.prover.as_ref(),
+                    progress,
+                },
+                req.nft_utxo,
+                req.user_address,
+                req.funding_utxo,
+                req.funding_value,
+                UpdateOptions {
+                    extra_outputs: req
+                        .extra_outputs
+                        .into_iter()
+                        .map(|out| (out.address, out.sats))
+                        .collect(),
+                    expected_sessions: req.expected_sessions,
+                    ..Default::default()
+                },
+            )
+        },
+        log_progress("update"),
+    )
     .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    .map_err(api_error)?;
 
     Ok(ApiResponse {
         success: true,
         message: Some("Unsigned update transactions created".to_string()),
-        data: Some(unsigned),
+        data: Some(WithSession {
+            session_id: sessions.insert(&unsigned),
+            response: unsigned,
+        }),
+    })
+}
+
+async fn handle_rename_unsigned(
+    State(state): State<AppState>,
+    Json(req): Json<RenameNftRequest>,
+) -> Result<ApiResponse<WithSession<UnsignedUpdateResponse>>, ApiError> {
+    req.validate()?;
+
+    let sessions = state.sessions.clone();
+    let unsigned = run_with_progress(
+        move |progress| {
+            let btc = connect_bitcoin()?;
+            rename_nft_unsigned(
+                &btc,
+                &ReportingProver {
+                    inner: state.prover.as_ref(),
+                    progress,
+                },
+                req.nft_utxo,
+                req.new_name,
+                req.user_address,
+                req.funding_utxo,
+                req.funding_value,
+            )
+        },
+        log_progress("rename"),
+    )
+    .await
+    .map_err(api_error)?;
+
+    Ok(ApiResponse {
+        success: true,
+        message: Some("Unsigned rename transactions created".to_string()),
+        data: Some(WithSession {
+            session_id: sessions.insert(&unsigned),
+            response: unsigned,
+        }),
     })
 }
 
 async fn handle_view(
     Json(req): Json<ViewNftRequest>,
-) -> Result<ApiResponse<serde_json::Value>, (StatusCode, String)> {
-    let utxo = req.utxo.clone();
+) -> Result<ApiResponse<serde_json::Value>, ApiError> {
+    req.validate()?;
 
-    let (habit_name, sessions, _) = tokio::task::spawn_blocking(move || {
-        let (txid, _vout) = utxo
-            .split_once(':')
-            .ok_or_else(|| anyhow::anyhow!("Invalid UTXO format, expected txid:vout"))?;
+    let utxo = req.utxo.clone();
 
+    let (metadata, pending_utxo) = tokio::task::spawn_blocking(move || {
         let btc = connect_bitcoin()?;
+        fetch_nft_state(&btc, &utxo)
+    })
+    .await
+    .map_err(internal_error)?
+    .map_err(api_error)?;
+
+    let mut data = view_json(&req.utxo, metadata, local_contract_vk().as_deref());
+    data["pending"] = serde_json::json!(pending_utxo.is_some());
+    data["pending_utxo"] = serde_json::json!(pending_utxo);
+
+    Ok(ApiResponse {
+        success: true,
+        message: Some("NFT data retrieved".to_string()),
+        data: Some(data),
+    })
+}
 
-        extract_nft_metadata(&btc, txid)
+/// View payload shared by the single and batch view endpoints
+fn view_json(utxo: &str, metadata: NftMetadata, local_vk: Option<&str>) -> serde_json::Value {
+    serde_json::json!({
+        "utxo": utxo,
+        "habit_name": metadata.habit_name,
+        "sessions": metadata.total_sessions,
+        "app_id": metadata.app_id,
+        "vk_matches_local": local_vk.map(|vk| vk == metadata.vk),
+        "vk": metadata.vk,
+        "meta": metadata.meta,
+    })
+}
+
+async fn handle_view_batch(
+    Json(req): Json<BatchViewNftRequest>,
+) -> Result<ApiResponse<serde_json::Map<String, serde_json::Value>>, ApiError> {
+    if req.utxos.len() > MAX_BATCH_VIEW {
+        return Err(ApiError::Status(
+            StatusCode::BAD_REQUEST,
+            format!("At most {} UTXOs per batch", MAX_BATCH_VIEW),
+        ));
+    }
+
+    let results = tokio::task::spawn_blocking(move || {
+        let btc = connect_bitcoin()?;
+        Ok::<_, anyhow::Error>(fetch_nft_metadata_batch(&btc, &req.utxos))
     })
     .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    .map_err(internal_error)?
+    .map_err(api_error)?;
+
+    let local_vk = local_contract_vk();
+    let data = results
+        .into_iter()
+        .map(|(utxo, result)| {
+            let entry = match result {
+                Ok(metadata) => view_json(&utxo, metadata, local_vk.as_deref()),
+                Err(error) => serde_json::json!({ "error": error }),
+            };
+            (utxo, entry)
+        })
+        .collect();
 
     Ok(ApiResponse {
         success: true,
         message: Some("NFT data retrieved".to_string()),
+        data: Some(data),
+    })
+}
+
+async fn handle_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<ApiResponse<serde_json::Value>, ApiError> {
+    let unsigned = state.sessions.get(&id).ok_or_else(|| {
+        ApiError::Status(
+            StatusCode::NOT_FOUND,
+            format!("No session {} (expired or unknown)", id),
+        )
+    })?;
+
+    Ok(ApiResponse {
+        success: true,
+        message: Some("Session retrieved".to_string()),
+        data: Some(unsigned),
+    })
+}
+
+async fn handle_cost(
+    Json(req): Json<ViewNftRequest>,
+) -> Result<ApiResponse<LifetimeCost>, ApiError> {
+    req.validate()?;
+
+    let cost = tokio::task::spawn_blocking(move || {
+        let btc = connect_bitcoin()?;
+        lifetime_cost(&btc, &req.utxo)
+    })
+    .await
+    .map_err(internal_error)?
+    .map_err(api_error)?;
+
+    Ok(ApiResponse {
+        success: true,
+        message: Some("Lifetime cost".to_string()),
+        data: Some(cost),
+    })
+}
+
+async fn handle_status(
+    Json(req): Json<TxStatusRequest>,
+) -> Result<ApiResponse<ConfirmationDetail>, ApiError> {
+    req.validate()?;
+
+    let detail = tokio::task::spawn_blocking(move || {
+        let btc = connect_bitcoin()?;
+        confirmation_detail(&btc, &req.txid)
+    })
+    .await
+    .map_err(internal_error)?
+    .map_err(api_error)?;
+
+    Ok(ApiResponse {
+        success: true,
+        message: Some("Transaction status".to_string()),
+        data: Some(detail),
+    })
+}
+
+async fn handle_inspect(
+    Json(req): Json<InspectTxRequest>,
+) -> Result<ApiResponse<TxInspection>, ApiError> {
+    req.validate()?;
+
+    let inspection = tokio::task::spawn_blocking(move || {
+        let btc = connect_bitcoin()?;
+        inspect_signed_tx(&btc, &req.signed_hex)
+    })
+    .await
+    .map_err(internal_error)?
+    .map_err(api_error)?;
+
+    let message = if inspection.anomalies.is_empty() {
+        "Transaction looks good".to_string()
+    } else {
+        format!("{} anomalies found", inspection.anomalies.len())
+    };
+
+    Ok(ApiResponse {
+        success: true,
+        message: Some(message),
+        data: Some(inspection),
+    })
+}
+
+async fn handle_contract(State(state): State<AppState>) -> ApiResponse<ContractInfo> {
+    ApiResponse {
+        success: true,
+        message: Some("Contract info".to_string()),
+        data: Some(state.contract.as_ref().clone()),
+    }
+}
+
+async fn handle_locate(
+    Json(req): Json<LocateNftRequest>,
+) -> Result<ApiResponse<serde_json::Value>, ApiError> {
+    req.validate()?;
+
+    let app_id = req.app_id.clone();
+
+    let utxo = tokio::task::spawn_blocking(move || {
+        let btc = connect_bitcoin()?;
+        find_current_utxo_by_app_id(&btc, &app_id)
+    })
+    .await
+    .map_err(internal_error)?
+    .map_err(api_error)?
+    .ok_or_else(|| {
+        ApiError::Status(
+            StatusCode::NOT_FOUND,
+            format!("No unspent NFT with app id {}", req.app_id),
+        )
+    })?;
+
+    Ok(ApiResponse {
+        success: true,
+        message: Some("NFT located".to_string()),
         data: Some(serde_json::json!({
-            "utxo": req.utxo,
-            "habit_name": habit_name,
-            "sessions": sessions,
+            "app_id": req.app_id,
+            "utxo": utxo,
         })),
     })
 }
 
+// ============================================================================
+// Logging
+// ============================================================================
+
+/// Collects log key-values into a JSON object
+struct JsonFields(serde_json::Map<String, serde_json::Value>);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for JsonFields {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        let value = if let Some(n) = value.to_u64() {
+            serde_json::json!(n)
+        } else if let Some(n) = value.to_i64() {
+            serde_json::json!(n)
+        } else if let Some(b) = value.to_bool() {
+            serde_json::json!(b)
+        } else {
+            serde_json::json!(value.to_string())
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+/// Initialize logging from RUST_LOG. HABIT_LOG_FORMAT=json emits one JSON
+/// object per line (for ELK/Loki); the default `text` keeps the usual format.
+fn init_logging() {
+    let mut builder = env_logger::Builder::from_default_env();
+
+    match std::env::var("HABIT_LOG_FORMAT").as_deref() {
+        Ok("json") => {
+            builder.format(|buf, record| {
+                let mut fields = JsonFields(serde_json::Map::new());
+                let _ = record.key_values().visit(&mut fields);
+
+                let mut entry = serde_json::json!({
+                    "ts": chrono::Utc::now().to_rfc3339(),
+                    "level": record.level().to_string(),
+                    "target": record.target(),
+                    "msg": record.args().to_string(),
+                });
+                if let Some(obj) = entry.as_object_mut() {
+                    obj.extend(fields.0);
+                }
+                writeln!(buf, "{}", entry)
+            });
+        }
+        Ok("text") | Err(_) => {}
+        Ok(other) => eprintln!("Unknown HABIT_LOG_FORMAT '{}', using text", other),
+    }
+
+    builder.init();
+}
+
+/// Log method, path, status and latency of every API request
+async fn log_requests(req: Request, next: Next) -> Response {
+    let method = req.method().to_string();
+    let path = req.uri().path().to_string();
+    let started = std::time::Instant::now();
+
+    let response = next.run(req).await;
+
+    log::info!(
+        method = method.as_str(),
+        path = path.as_str(),
+        status = response.status().as_u16(),
+        latency_ms = started.elapsed().as_millis() as u64;
+        "Request handled"
+    );
+    response
+}
+
+/// Reject clients that exceed their rate limit with 429 + Retry-After
+async fn rate_limit(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<std::net::SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Response {
+    match state.rate_limiter.check(addr.ip()) {
+        Ok(()) => next.run(req).await,
+        Err(wait) => {
+            log::warn!(client = addr.ip().to_string().as_str(); "Rate limit exceeded");
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(
+                    axum::http::header::RETRY_AFTER,
+                    wait.as_secs().max(1).to_string(),
+                )],
+                "Rate limit exceeded",
+            )
+                .into_response()
+        }
+    }
+}
+
 // ============================================================================
 // Server & CLI Runners
 // ============================================================================
 
 async fn run_server() -> anyhow::Result<()> {
-    let app = Router::new()
+    // Every mutating endpoint needs the contract, so don't boot a server
+    // that would 500 on each create/update
+    let contract = match contract_info() {
+        Ok(contract) => contract,
+        Err(e) => {
+            log::error!("Contract artifacts missing, refusing to start: {}", e);
+            return Err(e.context("Cannot start API server without the contract"));
+        }
+    };
+    log::info!("Serving contract VK {}", contract.vk);
+
+    let state = AppState {
+        contract: Arc::new(contract),
+        prover: Arc::from(default_prover()),
+        sessions: Arc::new(SessionStore::from_env()),
+        rate_limiter: Arc::new(RateLimiter::from_env()),
+    };
+
+    // Endpoints that run the prover or broadcast are rate limited per IP
+    let limited = Router::new()
         .route("/api/nft/create/unsigned", post(handle_create_unsigned))
+        .route("/api/nft/create/batch", post(handle_create_batch))
         .route("/api/nft/update/unsigned", post(handle_update_unsigned))
+        .route("/api/nft/rename/unsigned", post(handle_rename_unsigned))
         .route("/api/nft/broadcast", post(handle_broadcast_nft))
+        .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit));
+
+    let app = Router::new()
+        .merge(limited)
         // .route("/api/nft/update", post(handle_update))
         .route("/api/nft/view", post(handle_view))
-        .layer(CorsLayer::permissive());
+        .route("/api/nft/view/batch", post(handle_view_batch))
+        .route("/api/nft/locate", post(handle_locate))
+        .route("/api/nft/cost", post(handle_cost))
+        .route("/api/nft/session/:id", get(handle_session))
+        .route("/api/nft/inspect", post(handle_inspect))
+        .route("/api/nft/status", post(handle_status))
+        .route("/api/contract", get(handle_contract))
+        .layer(middleware::from_fn(log_requests))
+        .layer(CorsLayer::permissive())
+        .with_state(state);
 
     let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await?;
 
     log::info!("Starting Habit Tracker API Server");
     log::info!("Listening on http://127.0.0.1:3000");
 
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await?;
     Ok(())
 }
 
-async fn run_cli(command: Commands) -> anyhow::Result<()> {
-    let btc = connect_bitcoin()?;
+/// Print a stage line as a long create/update moves along
+fn print_progress(stage: Progress) {
+    println!("⏳ {}", stage);
+}
 
+async fn run_cli(command: Commands) -> anyhow::Result<()> {
     match command {
-        Commands::Create { habit } => create_nft(&btc, habit).map(|_| ()),
-        Commands::Update { utxo } => update_nft(&btc, utxo).await,
-        Commands::View { utxo } => view_nft(&btc, utxo),
+        Commands::Create { habit, to } => run_with_progress(
+            move |progress| {
+                create_nft(
+                    &connect_bitcoin()?,
+                    &ReportingProver {
+                        inner: default_prover().as_ref(),
+                        progress,
+                    },
+                    habit,
+                    CreateOptions {
+                        to,
+                        progress: Some(progress.clone()),
+                        ..Default::default()
+                    },
+                )
+            },
+            print_progress,
+        )
+        .await
+        .map(|_| ()),
+        Commands::CreateMany { habits } => {
+            create_many(&connect_bitcoin()?, default_prover().as_ref(), habits)
+        }
+        Commands::Update { utxo } => {
+            run_with_progress(
+                move |progress| {
+                    update_nft(
+                        &connect_bitcoin()?,
+                        &ReportingProver {
+                            inner: default_prover().as_ref(),
+                            progress,
+                        },
+                        utxo,
+                        UpdateOptions {
+                            progress: Some(progress.clone()),
+                            ..Default::default()
+                        },
+                    )
+                },
+                print_progress,
+            )
+            .await
+        }
+        Commands::Rename { utxo, name } => {
+            rename_nft(&connect_bitcoin()?, default_prover().as_ref(), utxo, name)
+        }
+        Commands::CreateUnsigned { habit, out } => {
+            export_create_unsigned(&connect_bitcoin()?, default_prover().as_ref(), habit, &out)
+        }
+        Commands::UpdateUnsigned { utxo, out } => {
+            export_update_unsigned(&connect_bitcoin()?, default_prover().as_ref(), utxo, &out)
+        }
+        Commands::Broadcast { commit, spell } => {
+            broadcast_from_files(&connect_bitcoin()?, &commit, &spell)
+        }
+        Commands::View { utxo } => view_nft(&connect_bitcoin()?, utxo),
+        Commands::Cost { utxo } => print_cost(&connect_bitcoin()?, utxo),
+        Commands::Locate { app_id } => locate_nft(&connect_bitcoin()?, app_id),
+        Commands::Consolidate { fee_rate } => consolidate(&connect_bitcoin()?, fee_rate),
+        Commands::AppId { seed } => print_app_id(seed),
+        Commands::Doctor => doctor(),
+        Commands::Inspect { tx_hex } => inspect_tx(&connect_bitcoin()?, tx_hex),
+        Commands::ShowSpell { tx_hex, txid, file } => {
+            // Decoding inline hex or a file works offline
+            let btc = match txid {
+                Some(_) => Some(connect_bitcoin()?),
+                None => None,
+            };
+            show_spell(btc.as_ref(), tx_hex, txid, file)
+        }
     }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    init_logging();
     let cli = Cli::parse();
 
+    if let Some(network) = cli.network {
+        set_network_override(network.into(), cli.force_network);
+    }
+    if cli.contract_path.is_some() || cli.vk_path.is_some() {
+        set_contract_paths(cli.contract_path, cli.vk_path);
+    }
+
     match cli.command {
         Some(cmd) => {
             // CLI mode
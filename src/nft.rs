@@ -9,6 +9,7 @@ use charms_client::tx::Tx;
 use serde::Serialize;
 use serde_json::json;
 use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fs;
 use std::io::Write;
 use std::process::Command;
@@ -23,11 +24,53 @@ use tempfile::NamedTempFile;
 const NFT_AMOUNT_SATS: u64 = 1000;
 
 /// Minimum funding required for operations (covers NFT + fees)
-const MIN_FUNDING_SATS: u64 = 2000;
+pub const MIN_FUNDING_SATS: u64 = 2000;
 
-/// Default fee rate for transactions (sats/vB)
+/// RPC error message for a transaction the node can't look up
+const MISSING_TX_ERROR: &str = "no such mempool or blockchain transaction";
+
+/// Error the charms prover answers with (as a 400) when it doesn't have the
+/// WASM for an app's VK
+const MISSING_BINARIES_ERROR: &str = "missing app binaries for vks";
+
+/// Charms spell format version used by every spell we build
+pub const SPELL_VERSION: u32 = 8;
+
+/// Cap on the serialized `meta` object, so integrator data can't bloat the
+/// spell (and its fee)
+pub const MAX_META_BYTES: usize = 512;
+
+/// Default fee rate for transactions (sats/vB, override with HABIT_FEE_RATE)
 const DEFAULT_FEE_RATE: f64 = 2.0;
 
+/// Default lowest accepted fee rate (sats/vB, override with HABIT_FEE_RATE_MIN)
+const DEFAULT_FEE_RATE_MIN: f64 = 1.0;
+
+/// Default highest accepted fee rate (sats/vB, override with HABIT_FEE_RATE_MAX)
+const DEFAULT_FEE_RATE_MAX: f64 = 50.0;
+
+/// Estimated vsize of the commit tx (one funding input, one output)
+const COMMIT_TX_VSIZE: u64 = 150;
+
+/// Estimated vsize of the spell tx before the spell witness (inputs,
+/// NFT and change outputs)
+const SPELL_TX_BASE_VSIZE: u64 = 250;
+
+/// Witness bytes reserved for the proof that travels with the spell
+const PROOF_WITNESS_BYTES: u64 = 1024;
+
+/// Default namespace for NFT identity seeds (override with HABIT_IDENTITY_PREFIX)
+const DEFAULT_IDENTITY_PREFIX: &str = "habit_tracker";
+
+/// RPC error messages meaning a transaction was already accepted earlier
+/// (mempool or chain), so re-broadcasting it is a no-op rather than a failure
+const ALREADY_BROADCAST_ERRORS: &[&str] = &[
+    "transaction already in block chain",
+    "transaction outputs already in utxo set",
+    "txn-already-known",
+    "txn-already-in-mempool",
+];
+
 /// Badge milestones - The Samurai Path to Mastery (66 Days)
 const BADGE_MILESTONES: &[(u64, &str)] = &[
     // Stage 1: DESTRUCTION (Days 1-22) - Breaking Old Patterns
@@ -61,12 +104,50 @@ const BADGE_MILESTONES: &[(u64, &str)] = &[
 // Public Response Types
 // ============================================================================
 
-#[derive(Serialize)]
+#[derive(Serialize, Debug)]
 pub struct UnsignedNftResponse {
     pub commit_tx_hex: String,
     pub spell_tx_hex: String,
     pub commit_txid: String, // For reference
     pub spell_inputs_info: Vec<SigningInputInfo>,
+    /// Where the NFT will live once broadcast (see `predicted_nft_utxo`)
+    pub predicted_nft_utxo: String,
+}
+
+/// Optional knobs for an update
+#[derive(Default, Debug)]
+pub struct UpdateOptions {
+    /// Plain (address, sats) payments added after the NFT output
+    pub extra_outputs: Vec<(String, u64)>,
+    /// Sessions the caller believes the NFT has. If the chain disagrees the
+    /// caller is working from stale state and the update is refused.
+    pub expected_sessions: Option<u64>,
+    /// Receives the signing and broadcast stages. Proving stages come from
+    /// the prover; wrap it in a ReportingProver to see them.
+    pub progress: Option<ProgressSender>,
+}
+
+/// Optional knobs for a create
+#[derive(Default, Debug)]
+pub struct CreateOptions {
+    /// Mint the NFT here (e.g. cold storage) instead of to the funding
+    /// address. Funding and change still come from the funder.
+    pub to: Option<String>,
+    /// Free-form metadata stored under the charm's `meta`
+    pub meta: Option<HashMap<String, String>>,
+    /// Receives the signing and broadcast stages. Proving stages come from
+    /// the prover; wrap it in a ReportingProver to see them.
+    pub progress: Option<ProgressSender>,
+}
+
+/// Outcome of one habit in a bulk create
+#[derive(Serialize, Debug)]
+pub struct BatchCreateResult {
+    pub habit: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unsigned: Option<UnsignedNftResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 #[derive(Serialize, Debug)]
@@ -77,6 +158,8 @@ pub struct UnsignedUpdateResponse {
     pub spell_inputs_info: Vec<SigningInputInfo>,
     pub current_sessions: u64,
     pub new_sessions: u64,
+    /// Where the NFT will live once broadcast (see `predicted_nft_utxo`)
+    pub predicted_nft_utxo: String,
 }
 
 #[derive(Serialize, Debug)]
@@ -87,12 +170,59 @@ pub struct SigningInputInfo {
     pub amount_sats: u64,
 }
 
+/// Habit NFT state decoded from a spell, plus the contract that governs it
+#[derive(Serialize, Debug, Clone)]
+pub struct NftMetadata {
+    pub habit_name: String,
+    pub total_sessions: u64,
+    pub owner: String,
+    pub app_id: String, // n/{identity}/{vk}
+    pub vk: String,
+    /// Integrator key-values set at mint, carried forward on every update
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<HashMap<String, String>>,
+}
+
+/// Identity of the contract this server mints under
+#[derive(Serialize, Debug, Clone)]
+pub struct ContractInfo {
+    pub vk: String,
+    pub wasm_sha256: String,
+    pub spell_version: u32,
+}
+
 #[derive(Serialize)]
 pub struct BroadcastNftResponse {
     pub commit_txid: String,
     pub spell_txid: String,
 }
 
+/// Where a transaction was confirmed. Clients remember `block_hash` and
+/// treat a different hash at the same height as a reorg.
+#[derive(Serialize, Debug)]
+pub struct ConfirmationDetail {
+    pub txid: String,
+    /// 0 while in the mempool or when its block was reorged out
+    pub confirmations: u32,
+    pub block_hash: Option<String>,
+    pub block_height: Option<u64>,
+}
+
+/// What a signed transaction does, checked before it is broadcast
+#[derive(Serialize, Debug)]
+pub struct TxInspection {
+    pub txid: String,
+    pub num_inputs: usize,
+    pub num_outputs: usize,
+    /// Output 0 carries a habit charm
+    pub nft_output_present: bool,
+    pub total_out_sats: u64,
+    /// None when some prevout isn't in the UTXO set (e.g. unbroadcast commit)
+    pub fee: Option<u64>,
+    /// Anything that looks wrong; empty for a well-formed tx
+    pub anomalies: Vec<String>,
+}
+
 // ============================================================================
 // Utility Functions
 // ============================================================================
@@ -106,6 +236,20 @@ fn get_badges_for_sessions(sessions: u64) -> Vec<String> {
         .collect()
 }
 
+/// Check whether an RPC error means the node can't find a transaction, which
+/// on a node without -txindex just means it isn't in the mempool or wallet
+pub fn is_missing_tx_error(err: &bitcoincore_rpc::Error) -> bool {
+    err.to_string().to_lowercase().contains(MISSING_TX_ERROR)
+}
+
+/// Check whether an RPC error means the transaction was already broadcast
+fn is_already_broadcast_error(err: &bitcoincore_rpc::Error) -> bool {
+    let msg = err.to_string().to_lowercase();
+    ALREADY_BROADCAST_ERRORS
+        .iter()
+        .any(|known| msg.contains(known))
+}
+
 // #[derive(Debug, Clone, Copy, PartialEq)]
 // pub enum ProverBackend {
 //     _Http,
@@ -128,14 +272,60 @@ fn get_badges_for_sessions(sessions: u64) -> Vec<String> {
 //     }
 // }
 
-/// Get the path to the compiled contract WASM
+/// Contract artifact paths given on the command line (`--contract-path`,
+/// `--vk-path`), taking priority over the environment
+static CONTRACT_PATH_OVERRIDES: std::sync::OnceLock<(Option<PathBuf>, Option<PathBuf>)> =
+    std::sync::OnceLock::new();
+
+/// Override where the contract WASM and VK are read from. Only the first call takes effect.
+pub fn set_contract_paths(wasm: Option<PathBuf>, vk: Option<PathBuf>) {
+    if CONTRACT_PATH_OVERRIDES.set((wasm, vk)).is_err() {
+        log::warn!("Contract paths already set, ignoring override");
+    }
+}
+
+/// Resolve a contract artifact: CLI flag, then env var, then the source tree
+/// (which only exists in dev checkouts)
+fn contract_artifact_path(
+    flag: Option<&PathBuf>,
+    env_var: &str,
+    default: &str,
+) -> std::path::PathBuf {
+    if let Some(path) = flag {
+        return path.clone();
+    }
+    if let Ok(path) = env::var(env_var) {
+        return PathBuf::from(path);
+    }
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(default)
+}
+
+/// Get the path to the contract WASM (`--contract-path` / HABIT_CONTRACT_WASM)
 pub fn get_contract_path() -> std::path::PathBuf {
-    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("contracts/habit-tracker.wasm")
+    let flag = CONTRACT_PATH_OVERRIDES
+        .get()
+        .and_then(|(wasm, _)| wasm.as_ref());
+    contract_artifact_path(flag, "HABIT_CONTRACT_WASM", "contracts/habit-tracker.wasm")
 }
 
-/// Get the path to the contract verification key
+/// Get the path to the contract verification key (`--vk-path` / HABIT_CONTRACT_VK)
 pub fn get_contract_vk_path() -> std::path::PathBuf {
-    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("contracts/habit-tracker.vk")
+    let flag = CONTRACT_PATH_OVERRIDES
+        .get()
+        .and_then(|(_, vk)| vk.as_ref());
+    contract_artifact_path(flag, "HABIT_CONTRACT_VK", "contracts/habit-tracker.vk")
+}
+
+/// Read the locally-built contract VK, if `make contract` has been run
+pub fn local_contract_vk() -> Option<String> {
+    fs::read_to_string(get_contract_vk_path())
+        .ok()
+        .map(|vk| vk.trim().to_string())
+}
+
+/// Extract the verification key embedded in an app id (`n/{identity}/{vk}`)
+pub fn vk_from_app_id(app_id: &str) -> &str {
+    app_id.rsplit('/').next().unwrap_or_default()
 }
 
 /// Load contract WASM and verification key
@@ -144,7 +334,7 @@ pub fn load_contract() -> anyhow::Result<(String, String)> {
     if !contract_path.exists() {
         anyhow::bail!(
             "Contract WASM not found at {:?}\n\
-             Build it with: make contract",
+             Build it with: make contract, or point --contract-path / HABIT_CONTRACT_WASM at it",
             contract_path
         );
     }
@@ -156,7 +346,7 @@ pub fn load_contract() -> anyhow::Result<(String, String)> {
     } else {
         anyhow::bail!(
             "Contract VK not found at {:?}\n\
-             Build it with: make contract",
+             Build it with: make contract, or point --vk-path / HABIT_CONTRACT_VK at it",
             vk_path
         );
     };
@@ -168,13 +358,61 @@ pub fn load_contract() -> anyhow::Result<(String, String)> {
     Ok((vk, binary_base64))
 }
 
-/// Connect to Bitcoin Core RPC
-pub fn connect_bitcoin() -> anyhow::Result<Client> {
-    let (url, auth) = if std::env::var("USE_DOCKER").is_ok() {
-        // Docker regtest - must specify wallet in URL path
+/// Describe the local contract: its VK and a hash of the WASM binary
+pub fn contract_info() -> anyhow::Result<ContractInfo> {
+    let (vk, _binary_base64) = load_contract()?;
+    let binary_bytes = fs::read(get_contract_path())?;
+
+    Ok(ContractInfo {
+        vk,
+        wasm_sha256: hex::encode(Sha256::digest(&binary_bytes)),
+        spell_version: SPELL_VERSION,
+    })
+}
+
+/// Default wallet name (override with BITCOIN_WALLET_NAME)
+const DEFAULT_WALLET_NAME: &str = "test";
+
+/// Load the named wallet, creating it as a descriptor wallet if it doesn't
+/// exist yet, and return a client scoped to it
+pub fn ensure_wallet(
+    btc: &Client,
+    rpc_url: &str,
+    auth: Auth,
+    name: &str,
+) -> anyhow::Result<Client> {
+    if !btc.list_wallets()?.iter().any(|w| w == name) {
+        if btc.list_wallet_dir()?.iter().any(|w| w == name) {
+            log::debug!("Loading wallet '{}'", name);
+            btc.load_wallet(name)?;
+        } else {
+            log::info!("Creating descriptor wallet '{}'", name);
+            // createwallet name disable_private_keys blank passphrase avoid_reuse descriptors
+            btc.call::<serde_json::Value>(
+                "createwallet",
+                &[
+                    json!(name),
+                    json!(false),
+                    json!(false),
+                    json!(""),
+                    json!(false),
+                    json!(true),
+                ],
+            )?;
+        }
+    }
+
+    let wallet_url = format!("{}/wallet/{}", rpc_url.trim_end_matches('/'), name);
+    Ok(Client::new(&wallet_url, auth)?)
+}
+
+/// RPC URL and credentials of the node to talk to
+fn node_endpoint() -> anyhow::Result<(String, Auth)> {
+    Ok(if std::env::var("USE_DOCKER").is_ok() {
+        // Docker regtest
         log::debug!("Using Docker Bitcoin regtest");
         (
-            "http://127.0.0.1:18443/wallet/test".to_string(), // Added /wallet/test
+            "http://127.0.0.1:18443".to_string(),
             Auth::UserPass("test".to_string(), "test321".to_string()),
         )
     } else {
@@ -185,13 +423,28 @@ pub fn connect_bitcoin() -> anyhow::Result<Client> {
 
         log::debug!("Using testnet4 node");
         (
-            "http://127.0.0.1:48332/wallet/test".to_string(),
+            "http://127.0.0.1:48332".to_string(),
             Auth::CookieFile(cookie_path),
         )
-    };
+    })
+}
 
-    let btc = Client::new(&url, auth)?;
-    log::info!("Connected to Bitcoin Core RPC at {}", url);
+fn wallet_name() -> String {
+    env::var("BITCOIN_WALLET_NAME").unwrap_or_else(|_| DEFAULT_WALLET_NAME.to_string())
+}
+
+/// Connect to Bitcoin Core RPC
+pub fn connect_bitcoin() -> anyhow::Result<Client> {
+    let (url, auth) = node_endpoint()?;
+    let wallet_name = wallet_name();
+
+    let node = Client::new(&url, auth.clone())?;
+    let btc = ensure_wallet(&node, &url, auth, &wallet_name)?;
+    log::info!(
+        "Connected to Bitcoin Core RPC at {} (wallet '{}')",
+        url,
+        wallet_name
+    );
     Ok(btc)
 }
 // pub fn connect_bitcoin() -> anyhow::Result<Client> {
@@ -208,20 +461,150 @@ pub fn connect_bitcoin() -> anyhow::Result<Client> {
 //     Ok(btc)
 // }
 
-/// Get a suitable funding UTXO, excluding specified UTXOs
-pub fn get_funding_utxo(
+/// Network forced from the CLI (`--network`) and whether to skip the
+/// consistency check against the connected node (`--force-network`)
+static NETWORK_OVERRIDE: std::sync::OnceLock<(bitcoin::Network, bool)> = std::sync::OnceLock::new();
+
+/// Override the network reported by the node. Only the first call takes effect.
+pub fn set_network_override(network: bitcoin::Network, force: bool) {
+    if NETWORK_OVERRIDE.set((network, force)).is_err() {
+        log::warn!("Network override already set, ignoring {}", network);
+    }
+}
+
+/// Network to build for: the `--network` override if set, otherwise whatever
+/// the node reports. An override that disagrees with the node is rejected
+/// unless it was forced.
+pub fn resolve_network(btc: &Client) -> anyhow::Result<bitcoin::Network> {
+    let Some(&(network, force)) = NETWORK_OVERRIDE.get() else {
+        return Ok(btc.get_blockchain_info()?.chain);
+    };
+
+    if !force {
+        let detected = btc.get_blockchain_info()?.chain;
+        if detected != network {
+            anyhow::bail!(
+                "--network {} does not match the connected node ({}). Use --force-network to override",
+                network,
+                detected
+            );
+        }
+    }
+
+    Ok(network)
+}
+
+/// Whether the local prover should mock proofs. Mocked unless `--network`
+/// names a network other than regtest, so runs without the flag keep the
+/// existing mock proving.
+pub fn mock_proofs() -> bool {
+    match NETWORK_OVERRIDE.get() {
+        Some(&(network, _)) => network == bitcoin::Network::Regtest,
+        None => true,
+    }
+}
+
+/// Whether a wallet UTXO may be spent as plain funds. NFT_AMOUNT_SATS
+/// outputs are skipped outright; anything else is checked for a habit charm,
+/// since an NFT minted elsewhere may hold a different value. Spending one
+/// would destroy the charm.
+fn is_spendable_funding(
     btc: &Client,
-    exclude_utxo: Option<&str>,
-) -> anyhow::Result<(String, u64, String)> {
-    let utxos = btc.list_unspent(None, None, None, None, None)?;
-    let network = btc.get_blockchain_info()?.chain;
+    utxo: &bitcoincore_rpc::json::ListUnspentResultEntry,
+) -> anyhow::Result<bool> {
+    if utxo.amount.to_sat() == NFT_AMOUNT_SATS {
+        return Ok(false);
+    }
+
+    let spell = find_spell(btc, &utxo.txid.to_string()).map_err(|e| {
+        anyhow::anyhow!(
+            "Can't tell whether {}:{} holds a habit NFT: {:#}",
+            utxo.txid,
+            utxo.vout,
+            e
+        )
+    })?;
+    Ok(spell.is_none_or(|spell| spell["outs"][utxo.vout as usize]["charms"]["$0000"].is_null()))
+}
+
+/// Value held by an NFT output, read from chain (mempool included) rather
+/// than assumed, since an NFT minted elsewhere may not hold NFT_AMOUNT_SATS
+fn nft_output_sats(btc: &Client, nft_utxo: &str) -> anyhow::Result<u64> {
+    let (txid, vout) = parse_utxo(nft_utxo)?;
+    let tx_out = btc
+        .get_tx_out(&txid, vout, Some(true))?
+        .ok_or_else(|| anyhow::anyhow!("NFT output {} not found or already spent", nft_utxo))?;
+    Ok(tx_out.value.to_sat())
+}
+
+/// Funding UTXOs tried before giving up when broadcasts keep reporting the
+/// chosen one as missing or spent
+const FUNDING_ATTEMPTS: usize = 3;
 
-    let funding = utxos.iter().find(|utxo| {
+/// Run `attempt` with a funding UTXO (outpoint, sats, address), moving on to
+/// the next candidate if the node rejects the broadcast because that UTXO was
+/// spent in the meantime
+fn with_funding_retry<T>(
+    btc: &Client,
+    exclude: &[&str],
+    mut attempt: impl FnMut(String, u64, String) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let mut excluded: Vec<String> = exclude.iter().map(|u| u.to_string()).collect();
+    for remaining in (0..FUNDING_ATTEMPTS).rev() {
+        let excluded_refs: Vec<&str> = excluded.iter().map(String::as_str).collect();
+        let (funding_utxo, funding_value, addr_str) = get_funding_utxo(btc, &excluded_refs)?;
+
+        match attempt(funding_utxo.clone(), funding_value, addr_str) {
+            Err(e)
+                if remaining > 0
+                    && is_missing_inputs_error(&e)
+                    && is_spent(btc, &funding_utxo)? =>
+            {
+                log::warn!(
+                    "Funding UTXO {} was spent concurrently, retrying with another: {:#}",
+                    funding_utxo,
+                    e
+                );
+                excluded.push(funding_utxo);
+            }
+            result => return result,
+        }
+    }
+    unreachable!("last attempt always returns")
+}
+
+/// Whether an outpoint is gone from the UTXO set, counting mempool spends.
+/// Tells a funding UTXO taken by someone else apart from other missing
+/// inputs (e.g. an NFT that already moved), which no retry can fix.
+fn is_spent(btc: &Client, utxo: &str) -> anyhow::Result<bool> {
+    let (txid, vout) = parse_utxo(utxo)?;
+    Ok(btc.get_tx_out(&txid, vout, Some(true))?.is_none())
+}
+
+/// Whether the node rejected a transaction because an input is already
+/// spent or unknown
+fn is_missing_inputs_error(e: &anyhow::Error) -> bool {
+    let msg = format!("{:#}", e).to_lowercase();
+    msg.contains("missingorspent")
+        || msg.contains("missing inputs")
+        || msg.contains("missing-inputs")
+}
+
+/// Get a suitable funding UTXO, excluding specified UTXOs. Unconfirmed
+/// change counts; outputs the wallet already spent in the mempool are not
+/// listed by the node.
+pub fn get_funding_utxo(btc: &Client, exclude: &[&str]) -> anyhow::Result<(String, u64, String)> {
+    let utxos = btc.list_unspent(Some(0), None, None, None, None)?;
+    let network = resolve_network(btc)?;
+
+    let mut funding = None;
+    for utxo in &utxos {
         let utxo_id = format!("{}:{}", utxo.txid, utxo.vout);
-        let is_nft = utxo.amount.to_sat() == 1000;
-        let is_excluded = exclude_utxo.is_some_and(|excluded| utxo_id == excluded);
-        !is_nft && !is_excluded
-    });
+        if !exclude.contains(&utxo_id.as_str()) && is_spendable_funding(btc, utxo)? {
+            funding = Some(utxo);
+            break;
+        }
+    }
 
     if let Some(funding) = funding {
         let addr = funding
@@ -252,34 +635,391 @@ pub fn get_funding_utxo(
     }
 }
 
-/// Generate a unique app ID for this spell
-fn generate_app_id(vk: &str) -> String {
-    let identity_input = format!("habit_tracker_{}", chrono::Utc::now().timestamp());
-    let mut hasher = Sha256::new();
-    hasher.update(identity_input.as_bytes());
-    let identity_hash = hasher.finalize();
-    let identity_hex = hex::encode(identity_hash);
+/// Address types the signing flow knows how to build prevouts for
+const SUPPORTED_ADDRESS_TYPES: &[bitcoin::AddressType] =
+    &[bitcoin::AddressType::P2wpkh, bitcoin::AddressType::P2tr];
+
+/// Reject owner/change addresses whose script type the signing step can't
+/// handle, before spending time in the prover
+pub fn validate_address_type(address: &str) -> anyhow::Result<()> {
+    let parsed = bitcoin::Address::from_str(address)
+        .map_err(|e| anyhow::anyhow!("Invalid address {}: {}", address, e))?
+        .assume_checked();
+
+    match parsed.address_type() {
+        Some(kind) if SUPPORTED_ADDRESS_TYPES.contains(&kind) => Ok(()),
+        kind => anyhow::bail!(
+            "Unsupported address type {} for {}: use a P2WPKH (bc1q...) or P2TR (bc1p...) address",
+            kind.map(|k| k.to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+            address
+        ),
+    }
+}
+
+/// Fail unless the connected wallet holds the keys for `address`, since
+/// anything it can't sign for only fails after the prover has run
+pub fn ensure_wallet_owns_address(btc: &Client, address: &str, what: &str) -> anyhow::Result<()> {
+    let parsed = bitcoin::Address::from_str(address)
+        .map_err(|e| anyhow::anyhow!("Invalid {} address {}: {}", what, address, e))?
+        .assume_checked();
+
+    if btc.get_address_info(&parsed)?.is_mine != Some(true) {
+        anyhow::bail!(
+            "{} address {} is not controlled by wallet '{}', so it can't be signed for",
+            what,
+            address,
+            wallet_name()
+        );
+    }
+    Ok(())
+}
+
+/// Fail unless `utxo` is unspent and pays an address the connected wallet
+/// controls
+pub fn ensure_wallet_owns_utxo(btc: &Client, utxo: &str, what: &str) -> anyhow::Result<()> {
+    let (txid, vout) = parse_utxo(utxo)?;
+    let tx_out = btc
+        .get_tx_out(&txid, vout, Some(true))?
+        .ok_or_else(|| anyhow::anyhow!("{} UTXO {} not found or already spent", what, utxo))?;
+    let address = tx_out
+        .script_pub_key
+        .address
+        .ok_or_else(|| anyhow::anyhow!("{} UTXO {} has no address", what, utxo))?
+        .assume_checked();
+
+    ensure_wallet_owns_address(btc, &address.to_string(), what)
+        .map_err(|e| anyhow::anyhow!("{} UTXO {}: {}", what, utxo, e))
+}
+
+/// Pick `count` distinct funding UTXOs, largest first, so each can pay for its
+/// own create. Fails up front if the wallet doesn't have enough.
+pub fn select_funding_utxos(btc: &Client, count: usize) -> anyhow::Result<Vec<(String, u64)>> {
+    let mut utxos = Vec::new();
+    for utxo in btc.list_unspent(None, None, None, None, None)? {
+        if utxo.amount.to_sat() >= MIN_FUNDING_SATS && is_spendable_funding(btc, &utxo)? {
+            utxos.push(utxo);
+        }
+    }
+
+    if utxos.len() < count {
+        anyhow::bail!(
+            "Need {} funding UTXOs of at least {} sats, wallet has {}",
+            count,
+            MIN_FUNDING_SATS,
+            utxos.len()
+        );
+    }
+
+    utxos.sort_by_key(|utxo| std::cmp::Reverse(utxo.amount));
+    Ok(utxos
+        .into_iter()
+        .take(count)
+        .map(|utxo| (format!("{}:{}", utxo.txid, utxo.vout), utxo.amount.to_sat()))
+        .collect())
+}
+
+/// The NFT output of a spell tx, known before signing. Signing doesn't change
+/// the txid because every input we sign is segwit (P2WPKH funding, taproot
+/// commit, P2WPKH/P2TR NFT), so signatures only land in the witness.
+fn predicted_nft_utxo(spell_tx: &bitcoin::Transaction) -> String {
+    format!("{}:0", spell_tx.compute_txid())
+}
+
+/// Estimated vsize per input and output of a consolidation tx (segwit)
+const SWEEP_INPUT_VSIZE: u64 = 68;
+const SWEEP_OUTPUT_VSIZE: u64 = 43;
+const SWEEP_OVERHEAD_VSIZE: u64 = 11;
+
+/// Result of sweeping the wallet's plain funds into one output
+#[derive(Serialize, Debug)]
+pub struct ConsolidationResult {
+    pub inputs: usize,
+    pub utxo: String,
+    pub amount_sats: u64,
+    pub fee_sats: u64,
+}
+
+/// Sweep every non-NFT wallet UTXO into a single output at a new address
+pub fn consolidate_utxos(
+    btc: &Client,
+    fee_rate: Option<f64>,
+) -> anyhow::Result<ConsolidationResult> {
+    let fee_rate = match fee_rate {
+        Some(rate) => check_fee_rate(rate)?,
+        None => self::fee_rate()?,
+    };
+
+    let mut utxos = Vec::new();
+    for utxo in btc.list_unspent(None, None, None, None, None)? {
+        if is_spendable_funding(btc, &utxo)? {
+            utxos.push(utxo);
+        }
+    }
+    if utxos.len() < 2 {
+        anyhow::bail!("Nothing to consolidate: {} spendable UTXO(s)", utxos.len());
+    }
+
+    let total: u64 = utxos.iter().map(|utxo| utxo.amount.to_sat()).sum();
+    let vsize = SWEEP_OVERHEAD_VSIZE + SWEEP_INPUT_VSIZE * utxos.len() as u64 + SWEEP_OUTPUT_VSIZE;
+    let fee = (vsize as f64 * fee_rate).ceil() as u64;
+    let amount = total
+        .checked_sub(fee)
+        .filter(|amount| *amount > NFT_AMOUNT_SATS)
+        .ok_or_else(|| anyhow::anyhow!("{} sats can't cover a {} sat fee", total, fee))?;
+
+    let destination = btc
+        .get_new_address(None, None)?
+        .require_network(resolve_network(btc)?)?;
+
+    let tx = bitcoin::Transaction {
+        version: bitcoin::transaction::Version::TWO,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: utxos
+            .iter()
+            .map(|utxo| bitcoin::TxIn {
+                previous_output: bitcoin::OutPoint {
+                    txid: utxo.txid,
+                    vout: utxo.vout,
+                },
+                ..Default::default()
+            })
+            .collect(),
+        output: vec![bitcoin::TxOut {
+            value: bitcoin::Amount::from_sat(amount),
+            script_pubkey: destination.script_pubkey(),
+        }],
+    };
+
+    let signed = btc.sign_raw_transaction_with_wallet(&tx, None, None)?;
+    ensure_signed("consolidation", &signed)?;
+    let txid = btc.send_raw_transaction(&signed.hex)?;
+
+    log::info!("Consolidated {} UTXOs into {}:0", utxos.len(), txid);
+    Ok(ConsolidationResult {
+        inputs: utxos.len(),
+        utxo: format!("{}:0", txid),
+        amount_sats: amount,
+        fee_sats: fee,
+    })
+}
+
+/// Consolidate wallet funds and print what happened
+pub fn consolidate(btc: &Client, fee_rate: Option<f64>) -> anyhow::Result<()> {
+    let result = consolidate_utxos(btc, fee_rate)?;
+    println!(
+        "Consolidated {} UTXOs into {} ({} sats, fee {} sats)",
+        result.inputs, result.utxo, result.amount_sats, result.fee_sats
+    );
+    Ok(())
+}
+
+/// Nanosecond nonce stored in update charms so two updates built in the same
+/// second never produce identical transactions. The contract ignores it.
+fn spell_nonce() -> u64 {
+    chrono::Utc::now()
+        .timestamp_nanos_opt()
+        .unwrap_or_default()
+        .unsigned_abs()
+}
+
+/// Check mint metadata fits under MAX_META_BYTES once serialized
+pub fn validate_meta(meta: &HashMap<String, String>) -> anyhow::Result<()> {
+    let size = serde_json::to_vec(meta)?.len();
+    if size > MAX_META_BYTES {
+        anyhow::bail!(
+            "Metadata is {} bytes serialized, limit is {}",
+            size,
+            MAX_META_BYTES
+        );
+    }
+    Ok(())
+}
+
+/// Add `meta` to every habit charm in the spell. It's namespaced under its
+/// own key so it can't collide with the fields the contract checks.
+fn attach_meta(spell: &mut serde_json::Value, meta: Option<&HashMap<String, String>>) {
+    let Some(meta) = meta.filter(|m| !m.is_empty()) else {
+        return;
+    };
+    for side in ["ins", "outs"] {
+        if let Some(entries) = spell[side].as_array_mut() {
+            for entry in entries {
+                if let Some(charm) = entry.pointer_mut("/charms/$00") {
+                    charm["meta"] = json!(meta);
+                }
+            }
+        }
+    }
+}
+
+/// Namespace prefix for identity seeds, so deployments sharing the contract
+/// can tell their NFTs apart
+fn identity_prefix() -> String {
+    env::var("HABIT_IDENTITY_PREFIX").unwrap_or_else(|_| DEFAULT_IDENTITY_PREFIX.to_string())
+}
+
+/// App id for an identity seed: `n/{sha256(seed)}/{vk}`
+pub fn compute_app_id(identity_seed: &str, vk: &str) -> String {
+    let identity_hex = hex::encode(Sha256::digest(identity_seed.as_bytes()));
     format!("n/{}/{}", identity_hex, vk)
 }
 
+/// Identity seed for a new mint: `{prefix}_{unix_ts}_{funding txid:vout}`.
+/// Funding UTXOs are single-use, so mixing one in keeps creates built in
+/// the same second (e.g. a bulk create) from sharing an identity
+fn identity_seed(funding_utxo: &str) -> String {
+    format!(
+        "{}_{}_{}",
+        identity_prefix(),
+        chrono::Utc::now().timestamp(),
+        funding_utxo
+    )
+}
+
+/// Print the app id a seed maps to under the local contract VK
+pub fn print_app_id(seed: String) -> anyhow::Result<()> {
+    let (vk, _binary_base64) = load_contract()?;
+    println!("{}", compute_app_id(&seed, &vk));
+    Ok(())
+}
+
 // ============================================================================
 // NFT Metadata Operations
 // ============================================================================
 
-pub fn extract_nft_metadata(btc: &Client, txid: &str) -> anyhow::Result<(String, u64, String)> {
-    log::debug!("Extracting NFT metadata from {}", txid);
-
-    let tx_hex = btc.get_raw_transaction_hex(&bitcoin::Txid::from_str(txid)?, None)?;
+/// Decode the spell embedded in a raw transaction
+pub fn decode_spell_hex(tx_hex: &str) -> anyhow::Result<serde_json::Value> {
+    find_spell_hex(tx_hex)?.ok_or_else(|| anyhow::anyhow!("No spell found in the transaction"))
+}
 
-    let spell_output = Command::new("charms")
-        .args(["tx", "show-spell", "--tx", &tx_hex, "--mock", "--json"])
+/// Decode the spell of a raw transaction, or None if it carries none, in
+/// which case `charms` succeeds without printing anything
+fn find_spell_hex(tx_hex: &str) -> anyhow::Result<Option<serde_json::Value>> {
+    let spell_output = Command::new(find_charms_binary()?)
+        .args(["tx", "show-spell", "--tx", tx_hex, "--mock", "--json"])
         .output()?;
 
     if !spell_output.status.success() {
         anyhow::bail!("Failed to extract spell");
     }
+    if spell_output.stdout.trim_ascii().is_empty() {
+        return Ok(None);
+    }
 
-    let spell: serde_json::Value = serde_json::from_slice(&spell_output.stdout)?;
+    Ok(Some(serde_json::from_slice(&spell_output.stdout)?))
+}
+
+/// Fetch a raw transaction. Without -txindex the node only knows mempool
+/// and wallet transactions, so fall back to the wallet before giving up.
+pub fn fetch_raw_tx_hex(btc: &Client, txid: &bitcoin::Txid) -> anyhow::Result<String> {
+    match btc.get_raw_transaction_hex(txid, None) {
+        Ok(tx_hex) => Ok(tx_hex),
+        Err(e) if is_missing_tx_error(&e) => {
+            log::debug!("getrawtransaction missed {}, trying wallet: {}", txid, e);
+            match btc.get_transaction(txid, None) {
+                Ok(wallet_tx) => Ok(hex::encode(wallet_tx.hex)),
+                Err(wallet_err) => anyhow::bail!(
+                    "Transaction {} not found: it isn't in the mempool or this wallet ({}). \
+                     Looking up arbitrary confirmed transactions needs a node started with -txindex=1",
+                    txid,
+                    wallet_err
+                ),
+            }
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Decoded spells kept per txid (HABIT_SPELL_CACHE_SIZE, 0 disables)
+const DEFAULT_SPELL_CACHE_SIZE: usize = 256;
+
+static SPELL_CACHE: std::sync::OnceLock<
+    Option<std::sync::Mutex<lru::LruCache<String, serde_json::Value>>>,
+> = std::sync::OnceLock::new();
+
+/// Run `f` against the spell cache, or return None if caching is disabled
+pub fn with_spell_cache<T>(
+    f: impl FnOnce(&mut lru::LruCache<String, serde_json::Value>) -> T,
+) -> Option<T> {
+    let cache = SPELL_CACHE.get_or_init(|| {
+        let size = env::var("HABIT_SPELL_CACHE_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SPELL_CACHE_SIZE);
+        std::num::NonZeroUsize::new(size)
+            .map(|size| std::sync::Mutex::new(lru::LruCache::new(size)))
+    });
+    cache
+        .as_ref()
+        .map(|cache| f(&mut cache.lock().unwrap_or_else(|e| e.into_inner())))
+}
+
+/// Drop cached spells of the transactions `tx` spends. Those NFTs have moved
+/// on, so their entries would only push out the ones still being viewed.
+pub fn forget_spent_spells(tx: &bitcoin::Transaction) {
+    with_spell_cache(|cache| {
+        for input in &tx.input {
+            cache.pop(&input.previous_output.txid.to_string());
+        }
+    });
+}
+
+/// Decode the spell embedded in a transaction fetched from the node
+fn decode_spell(btc: &Client, txid: &str) -> anyhow::Result<serde_json::Value> {
+    find_spell(btc, txid)?.ok_or_else(|| anyhow::anyhow!("No spell found in {}", txid))
+}
+
+/// The spell embedded in a transaction fetched from the node, or None if it
+/// carries none. Running `charms` is the slow part of viewing, listing and
+/// picking funding, so results are cached, a missing spell as null.
+fn find_spell(btc: &Client, txid: &str) -> anyhow::Result<Option<serde_json::Value>> {
+    if let Some(spell) = with_spell_cache(|cache| cache.get(txid).cloned()).flatten() {
+        log::debug!("Spell cache hit for {}", txid);
+        return Ok(Some(spell).filter(|spell| !spell.is_null()));
+    }
+
+    let tx_hex = fetch_raw_tx_hex(btc, &bitcoin::Txid::from_str(txid)?)?;
+    let tx: bitcoin::Transaction = bitcoin::consensus::deserialize(&hex::decode(&tx_hex)?)?;
+    // A coinbase never carries a spell, and the wallet is full of them
+    let spell = if tx.is_coinbase() {
+        None
+    } else {
+        find_spell_hex(&tx_hex)?
+    };
+    with_spell_cache(|cache| cache.put(txid.to_string(), spell.clone().unwrap_or_default()));
+    Ok(spell)
+}
+
+/// Pretty-print the spell of a tx given as inline hex, a txid, or a hex file
+pub fn show_spell(
+    btc: Option<&Client>,
+    tx_hex: Option<String>,
+    txid: Option<String>,
+    file: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let spell = match (tx_hex, txid, file) {
+        (Some(tx_hex), _, _) => decode_spell_hex(tx_hex.trim())?,
+        (_, Some(txid), _) => {
+            let btc = btc.ok_or_else(|| anyhow::anyhow!("Looking up a txid needs a node"))?;
+            decode_spell(btc, &txid)?
+        }
+        (_, _, Some(path)) => {
+            let tx_hex = fs::read_to_string(&path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {:?}: {}", path, e))?;
+            decode_spell_hex(tx_hex.trim())?
+        }
+        _ => anyhow::bail!("Provide one of --tx-hex, --txid or --file"),
+    };
+
+    println!("{}", serde_json::to_string_pretty(&spell)?);
+    Ok(())
+}
+
+pub fn fetch_nft_metadata(btc: &Client, txid: &str) -> anyhow::Result<NftMetadata> {
+    log::debug!("Extracting NFT metadata from {}", txid);
+
+    let spell = decode_spell(btc, txid)?;
 
     let charms = spell
         .get("outs")
@@ -292,13 +1032,13 @@ pub fn extract_nft_metadata(btc: &Client, txid: &str) -> anyhow::Result<(String,
     let habit_name = charms
         .get("habit_name")
         .and_then(|v| v.as_str())
-        .unwrap_or("Meditation")
+        .ok_or_else(|| anyhow::anyhow!("No habit_name found in NFT"))?
         .to_string();
 
     let sessions = charms
         .get("total_sessions")
         .and_then(|v| v.as_u64())
-        .unwrap_or(0);
+        .ok_or_else(|| anyhow::anyhow!("No total_sessions found in NFT"))?;
 
     let owner = charms
         .get("owner")
@@ -306,6 +1046,32 @@ pub fn extract_nft_metadata(btc: &Client, txid: &str) -> anyhow::Result<(String,
         .ok_or_else(|| anyhow::anyhow!("No owner found in NFT"))?
         .to_string();
 
+    // Older NFTs have no meta. Only string values are accepted: updates
+    // write meta back as strings, which would silently retype anything else.
+    let meta = match charms.get("meta").filter(|v| !v.is_null()) {
+        Some(meta) => Some(
+            meta.as_object()
+                .ok_or_else(|| anyhow::anyhow!("NFT meta is not an object"))?
+                .iter()
+                .map(|(k, v)| {
+                    v.as_str()
+                        .map(|v| (k.clone(), v.to_string()))
+                        .ok_or_else(|| anyhow::anyhow!("NFT meta '{}' is not a string: {}", k, v))
+                })
+                .collect::<anyhow::Result<HashMap<_, _>>>()?,
+        ),
+        None => None,
+    };
+
+    // The habit charm is the first (and only) app in our spells
+    let app_id = spell
+        .get("apps")
+        .and_then(|v| v.as_object())
+        .and_then(|apps| apps.values().next())
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("No app id found in spell"))?
+        .to_string();
+
     log::info!(
         "NFT metadata - Habit: '{}', Sessions: {}, Owner: {}",
         habit_name,
@@ -313,7 +1079,91 @@ pub fn extract_nft_metadata(btc: &Client, txid: &str) -> anyhow::Result<(String,
         &owner[..12]
     );
 
-    Ok((habit_name, sessions, owner))
+    Ok(NftMetadata {
+        habit_name,
+        total_sessions: sessions,
+        owner,
+        vk: vk_from_app_id(&app_id).to_string(),
+        app_id,
+        meta,
+    })
+}
+
+/// Fetch metadata for many UTXOs, decoding each transaction only once.
+/// Failures are reported per UTXO instead of failing the whole batch.
+pub fn fetch_nft_metadata_batch(
+    btc: &Client,
+    utxos: &[String],
+) -> Vec<(String, Result<NftMetadata, String>)> {
+    let mut by_txid: std::collections::HashMap<String, Result<NftMetadata, String>> =
+        std::collections::HashMap::new();
+
+    utxos
+        .iter()
+        .map(|utxo| {
+            let result = match utxo.split_once(':') {
+                Some((txid, _vout)) => by_txid
+                    .entry(txid.to_string())
+                    .or_insert_with(|| fetch_nft_metadata(btc, txid).map_err(|e| e.to_string()))
+                    .clone(),
+                None => Err("Invalid UTXO format, expected txid:vout".to_string()),
+            };
+            (utxo.clone(), result)
+        })
+        .collect()
+}
+
+/// Find the wallet UTXO currently holding the NFT with the given app id
+pub fn find_current_utxo_by_app_id(btc: &Client, app_id: &str) -> anyhow::Result<Option<String>> {
+    let utxos = btc.list_unspent(None, None, None, None, None)?;
+
+    for utxo in utxos
+        .iter()
+        .filter(|u| u.amount.to_sat() == NFT_AMOUNT_SATS && u.vout == 0)
+    {
+        // Dust outputs that don't decode as habit NFTs are simply skipped
+        match fetch_nft_metadata(btc, &utxo.txid.to_string()) {
+            Ok(metadata) if metadata.app_id == app_id => {
+                return Ok(Some(format!("{}:{}", utxo.txid, utxo.vout)));
+            }
+            Ok(_) => {}
+            Err(e) => log::debug!("Skipping {}:{}: {}", utxo.txid, utxo.vout, e),
+        }
+    }
+
+    Ok(None)
+}
+
+/// Parse a `txid:vout` UTXO reference
+pub fn parse_utxo(utxo: &str) -> anyhow::Result<(bitcoin::Txid, u32)> {
+    let (txid, vout) = utxo
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("Invalid UTXO format, expected txid:vout"))?;
+    Ok((bitcoin::Txid::from_str(txid)?, vout.parse()?))
+}
+
+/// Check that a UTXO is an unspent habit NFT before building an update on it
+pub fn ensure_habit_nft(btc: &Client, nft_utxo: &str) -> anyhow::Result<NftMetadata> {
+    let (txid, vout) = parse_utxo(nft_utxo)?;
+
+    // Our spells always place the habit charm on output 0
+    if vout != 0 {
+        anyhow::bail!(
+            "UTXO {} is not a habit NFT: charm lives at vout 0",
+            nft_utxo
+        );
+    }
+
+    btc.get_tx_out(&txid, vout, Some(true))?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "UTXO {} is not a habit NFT: output not found or already spent",
+            nft_utxo
+        )
+    })?;
+
+    // Identified by its charm rather than its value, which updates carry over
+    fetch_nft_metadata(btc, &txid.to_string())
+        .map_err(|e| anyhow::anyhow!("UTXO {} is not a habit NFT: {}", nft_utxo, e))
 }
 
 // ============================================================================
@@ -321,7 +1171,7 @@ pub fn extract_nft_metadata(btc: &Client, txid: &str) -> anyhow::Result<(String,
 // ============================================================================
 
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 fn find_charms_binary() -> anyhow::Result<PathBuf> {
     // 1. Check environment variable first (highest priority)
@@ -359,6 +1209,166 @@ fn find_charms_binary() -> anyhow::Result<PathBuf> {
     )
 }
 
+fn env_f64(name: &str, default: f64) -> anyhow::Result<f64> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| anyhow::anyhow!("{} must be a number, got '{}'", name, value)),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Reject fee rates outside HABIT_FEE_RATE_MIN..=HABIT_FEE_RATE_MAX so a typo
+/// like 200 for 2.0 can't overpay
+pub fn check_fee_rate(fee_rate: f64) -> anyhow::Result<f64> {
+    let min = env_f64("HABIT_FEE_RATE_MIN", DEFAULT_FEE_RATE_MIN)?;
+    let max = env_f64("HABIT_FEE_RATE_MAX", DEFAULT_FEE_RATE_MAX)?;
+
+    if !(min..=max).contains(&fee_rate) {
+        anyhow::bail!(
+            "Fee rate {} sat/vB is outside the allowed range {}..={} sat/vB",
+            fee_rate,
+            min,
+            max
+        );
+    }
+    Ok(fee_rate)
+}
+
+/// Fee rate to prove with: HABIT_FEE_RATE or the default, within bounds
+fn fee_rate() -> anyhow::Result<f64> {
+    check_fee_rate(env_f64("HABIT_FEE_RATE", DEFAULT_FEE_RATE)?)
+}
+
+/// Estimate the fee for the commit + spell transactions carrying `spell`
+fn estimate_spell_fee(spell: &serde_json::Value, fee_rate: f64) -> u64 {
+    // Spell and proof ride in the witness, which counts a quarter
+    let witness_bytes = spell.to_string().len() as u64 + PROOF_WITNESS_BYTES;
+    let vsize = COMMIT_TX_VSIZE + SPELL_TX_BASE_VSIZE + witness_bytes.div_ceil(4);
+    (vsize as f64 * fee_rate).ceil() as u64
+}
+
+/// Check the funding UTXO covers every output of the spell plus fees before
+/// handing it to the prover, which otherwise fails with an opaque error.
+/// `input_sats` is what the spell's own inputs (e.g. the previous NFT) bring in.
+fn ensure_funding_covers(
+    spell: &serde_json::Value,
+    input_sats: u64,
+    funding_value: u64,
+    fee_rate: f64,
+) -> anyhow::Result<()> {
+    let output_sats: u64 = spell["outs"]
+        .as_array()
+        .map(|outs| outs.iter().filter_map(|out| out["sats"].as_u64()).sum())
+        .unwrap_or(0);
+    let fee = estimate_spell_fee(spell, fee_rate);
+    let required = (output_sats + fee).saturating_sub(input_sats);
+
+    if funding_value < required {
+        anyhow::bail!(
+            "Insufficient funds. Have {} sats, need {} sats ({} in outputs + ~{} fee at {} sat/vB{}), short by {} sats",
+            funding_value,
+            required,
+            output_sats,
+            fee,
+            fee_rate,
+            if input_sats > 0 {
+                format!(", minus {} from spell inputs", input_sats)
+            } else {
+                String::new()
+            },
+            required - funding_value
+        );
+    }
+    Ok(())
+}
+
+/// Why a proof couldn't be produced, so callers can tell a transient outage
+/// (worth retrying) from a spell the prover refused
+#[derive(Debug)]
+pub enum ProverError {
+    /// The prover couldn't be run at all (binary missing or failed to start)
+    Unavailable(String),
+    /// The prover ran but rejected the spell
+    Rejected(String),
+    /// A remote prover refused our credentials (401/403)
+    Unauthorized(String),
+}
+
+impl std::fmt::Display for ProverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProverError::Unavailable(msg) => write!(f, "Prover unavailable: {}", msg),
+            ProverError::Rejected(msg) => write!(f, "charms spell prove failed: {}", msg),
+            ProverError::Unauthorized(msg) => write!(f, "Prover refused credentials: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ProverError {}
+
+/// Turns a spell into the commit and spell transactions that carry it
+pub trait Prover: Send + Sync {
+    fn prove(
+        &self,
+        spell: &serde_json::Value,
+        prev_txs: &[String],
+        funding_utxo: &str,
+        funding_utxo_value: u64,
+        change_address: &str,
+        fee_rate: f64,
+    ) -> anyhow::Result<Vec<Tx>>;
+
+    /// VK of the contract this prover proves against; app ids are built on it
+    fn vk(&self) -> anyhow::Result<String> {
+        load_contract().map(|(vk, _binary_base64)| vk)
+    }
+}
+
+/// Proves by shelling out to the `charms` binary against the local contract
+pub struct CliProver {
+    pub contract_path: PathBuf,
+    /// Pass `--mock` to the prover
+    pub mock: bool,
+}
+
+impl Default for CliProver {
+    fn default() -> Self {
+        Self {
+            contract_path: get_contract_path(),
+            mock: mock_proofs(),
+        }
+    }
+}
+
+impl Prover for CliProver {
+    fn prove(
+        &self,
+        spell: &serde_json::Value,
+        prev_txs: &[String],
+        funding_utxo: &str,
+        funding_utxo_value: u64,
+        change_address: &str,
+        fee_rate: f64,
+    ) -> anyhow::Result<Vec<Tx>> {
+        let contract_path = self
+            .contract_path
+            .to_str()
+            .ok_or_else(|| anyhow::anyhow!("Contract path is not valid UTF-8"))?;
+        prove_with_cli(
+            spell,
+            contract_path,
+            prev_txs,
+            funding_utxo,
+            funding_utxo_value,
+            change_address,
+            fee_rate,
+            self.mock,
+        )
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn prove_with_cli(
     spell: &serde_json::Value,
     contract_path: &str,
@@ -367,6 +1377,7 @@ pub fn prove_with_cli(
     funding_utxo_value: u64,
     change_address: &str,
     fee_rate: f64,
+    mock: bool,
 ) -> anyhow::Result<Vec<Tx>> {
     // Write spell to temporary file
     let mut spell_file = NamedTempFile::new()?;
@@ -374,7 +1385,8 @@ pub fn prove_with_cli(
     let spell_path = spell_file.path().to_str().unwrap();
 
     // Locate charms binary - REPLACED SECTION
-    let charms_bin = find_charms_binary()?;
+    let charms_bin =
+        find_charms_binary().map_err(|e| ProverError::Unavailable(format!("{:#}", e)))?;
     log::debug!("Using charms binary: {:?}", charms_bin);
 
     // Convert contract_path to absolute path
@@ -396,114 +1408,428 @@ pub fn prove_with_cli(
         .arg(fee_rate.to_string())
         .arg("--chain")
         .arg("bitcoin")
-        .arg("--mock")
         .arg("--app-bins")
         .arg(absolute_contract_path);
 
-    if !prev_txs.is_empty() {
-        cmd.arg("--prev-txs").arg(prev_txs.join(","));
+    if mock {
+        cmd.arg("--mock");
+    }
+
+    if !prev_txs.is_empty() {
+        cmd.arg("--prev-txs").arg(prev_txs.join(","));
+    }
+
+    log::debug!("Calling prover...");
+    let started = std::time::Instant::now();
+    let output = cmd
+        .output()
+        .map_err(|e| ProverError::Unavailable(format!("failed to run {:?}: {}", charms_bin, e)))?;
+    log::info!(
+        prover_ms = started.elapsed().as_millis() as u64,
+        success = output.status.success();
+        "Prover finished"
+    );
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(ProverError::Rejected(stderr.into_owned()).into());
+    }
+
+    let stdout = String::from_utf8(output.stdout)?;
+    let txs: Vec<Tx> = serde_json::from_str(&stdout)
+        .map_err(|e| anyhow::anyhow!("Failed to parse CLI output: {}", e))?;
+
+    log::debug!("Prover generated {} transactions", txs.len());
+    Ok(txs)
+}
+
+/// Proves by POSTing the spell to a charms prover service
+pub struct HttpProver {
+    /// Base URL; `/spells/prove` is appended
+    pub url: String,
+    /// Sent as a bearer token when set
+    pub token: Option<String>,
+}
+
+impl HttpProver {
+    /// From CHARMS_PROVER_URL / CHARMS_PROVER_TOKEN; None when no URL is set
+    pub fn from_env() -> Option<Self> {
+        let url = std::env::var("CHARMS_PROVER_URL").ok()?;
+        Some(Self {
+            url,
+            token: std::env::var("CHARMS_PROVER_TOKEN").ok(),
+        })
+    }
+}
+
+impl Prover for HttpProver {
+    fn prove(
+        &self,
+        spell: &serde_json::Value,
+        prev_txs: &[String],
+        funding_utxo: &str,
+        funding_utxo_value: u64,
+        change_address: &str,
+        fee_rate: f64,
+    ) -> anyhow::Result<Vec<Tx>> {
+        let (vk, binary_base64) = load_contract()?;
+        prove_with_http(
+            &self.url,
+            self.token.as_deref(),
+            spell,
+            &vk,
+            &binary_base64,
+            prev_txs,
+            funding_utxo,
+            funding_utxo_value,
+            change_address,
+            fee_rate,
+        )
+    }
+}
+
+/// The remote prover when CHARMS_PROVER_URL is set, the local `charms`
+/// binary otherwise
+pub fn default_prover() -> Box<dyn Prover> {
+    match HttpProver::from_env() {
+        Some(http) => {
+            log::info!("Using remote prover at {}", http.url);
+            Box::new(http)
+        }
+        None => Box::new(CliProver::default()),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn prove_with_http(
+    base_url: &str,
+    token: Option<&str>,
+    spell: &serde_json::Value,
+    vk: &str,
+    binary_base64: &str,
+    prev_txs: &[String],
+    funding_utxo: &str,
+    funding_utxo_value: u64,
+    change_address: &str,
+    fee_rate: f64,
+) -> anyhow::Result<Vec<Tx>> {
+    let url = format!("{}/spells/prove", base_url.trim_end_matches('/'));
+    let prev_txs: Vec<_> = prev_txs.iter().map(|tx| json!({"bitcoin": tx})).collect();
+    let mut prover_request = json!({
+        "version": SPELL_VERSION,
+        "spell": spell,
+        "binaries": {},
+        "prev_txs": prev_txs,
+        "funding_utxo": funding_utxo,
+        "funding_utxo_value": funding_utxo_value,
+        "change_address": change_address,
+        "fee_rate": fee_rate,
+        "chain": "bitcoin"
+    });
+
+    let client = reqwest::blocking::Client::builder().timeout(std::time::Duration::from_secs(300));
+    let send = |client: &reqwest::blocking::Client,
+                prover_request: &serde_json::Value|
+     -> anyhow::Result<Vec<Tx>> {
+        let mut request = client.post(&url).json(prover_request);
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+
+        log::debug!("Calling prover at {}...", url);
+        let started = std::time::Instant::now();
+        let response = request
+            .send()
+            .map_err(|e| ProverError::Unavailable(format!("{}: {}", url, e)))?;
+        let status = response.status();
+        log::info!(
+            prover_ms = started.elapsed().as_millis() as u64,
+            success = status.is_success();
+            "Prover finished"
+        );
+
+        if !status.is_success() {
+            let body = response.text().unwrap_or_default();
+            let msg = format!("{} {}", status, body);
+            return Err(match status.as_u16() {
+                401 | 403 => ProverError::Unauthorized(msg),
+                502..=504 => ProverError::Unavailable(msg),
+                _ => ProverError::Rejected(msg),
+            }
+            .into());
+        }
+
+        response
+            .json()
+            .map_err(|e| anyhow::anyhow!("Failed to parse prover response: {}", e))
+    };
+
+    // A prover keeps binaries it has seen, so reference the contract by VK
+    // alone first and only upload the (large) WASM if it asks for it
+    let prove = || -> anyhow::Result<Vec<Tx>> {
+        let client = client.build()?;
+        match send(&client, &prover_request) {
+            Err(e) if is_unknown_binary_error(&e) => {
+                log::info!("Prover doesn't have contract {}, uploading binary", vk);
+                prover_request["binaries"] = json!({vk: binary_base64});
+                send(&client, &prover_request)
+            }
+            result => result,
+        }
+    };
+
+    // reqwest's blocking client panics on a tokio worker thread (the CLI
+    // proves from async code), so the request runs on its own thread
+    std::thread::scope(|scope| scope.spawn(prove).join())
+        .map_err(|_| anyhow::anyhow!("Prover request thread panicked"))?
+}
+
+/// Whether the prover rejected a request because it lacks a contract binary
+fn is_unknown_binary_error(e: &anyhow::Error) -> bool {
+    match e.downcast_ref::<ProverError>() {
+        Some(ProverError::Rejected(msg)) => {
+            msg.starts_with("400 ") && msg.contains(MISSING_BINARIES_ERROR)
+        }
+        _ => false,
+    }
+}
+
+/// Pick the commit and spell transactions out of the prover's output by
+/// structure rather than position: the spell tx is the one spending an
+/// output of the commit tx. Fails rather than guessing if that isn't a
+/// single unambiguous pair.
+pub fn classify_txs(txs: &[Tx]) -> anyhow::Result<(bitcoin::Transaction, bitcoin::Transaction)> {
+    let bitcoin_txs: Vec<&bitcoin::Transaction> = txs
+        .iter()
+        .filter_map(|tx| match tx {
+            Tx::Bitcoin(btx) => Some(btx.inner()),
+            _ => None,
+        })
+        .collect();
+
+    let mut pairs = Vec::new();
+    for commit in &bitcoin_txs {
+        let commit_txid = commit.compute_txid();
+        for spell in &bitcoin_txs {
+            if spell
+                .input
+                .iter()
+                .any(|input| input.previous_output.txid == commit_txid)
+            {
+                pairs.push((*commit, *spell));
+            }
+        }
+    }
+
+    match pairs.as_slice() {
+        [(commit, spell)] => Ok(((*commit).clone(), (*spell).clone())),
+        [] => anyhow::bail!(
+            "Prover returned {} bitcoin transactions but none spends another; \
+             can't tell the commit tx from the spell tx",
+            bitcoin_txs.len()
+        ),
+        _ => anyhow::bail!(
+            "Prover returned {} bitcoin transactions with {} possible commit/spell pairs; \
+             refusing to guess",
+            bitcoin_txs.len(),
+            pairs.len()
+        ),
+    }
+}
+
+// ============================================================================
+// Progress Reporting
+// ============================================================================
+
+/// Stage reached by a create or update, for callers that want to show more
+/// than a silent wait on the prover
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    ProvingStarted,
+    ProvingDone,
+    Signing,
+    Broadcasting,
+}
+
+impl std::fmt::Display for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Progress::ProvingStarted => "Proving spell (this can take a while)...",
+            Progress::ProvingDone => "Proof ready",
+            Progress::Signing => "Signing transactions...",
+            Progress::Broadcasting => "Broadcasting...",
+        })
     }
+}
 
-    log::debug!("Calling prover...");
-    let output = cmd.output()?;
+pub type ProgressSender = tokio::sync::mpsc::UnboundedSender<Progress>;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        anyhow::bail!("charms spell prove failed: {}", stderr);
+/// Send a progress event if anyone is listening. A dropped receiver just
+/// means nobody cares any more, so it isn't an error.
+fn report(progress: Option<&ProgressSender>, stage: Progress) {
+    if let Some(progress) = progress {
+        let _ = progress.send(stage);
+    }
+}
+
+/// Run a blocking create/update on the blocking pool, handing each progress
+/// event to `on_progress` as it happens rather than after the fact
+pub async fn run_with_progress<T: Send + 'static>(
+    op: impl FnOnce(&ProgressSender) -> anyhow::Result<T> + Send + 'static,
+    mut on_progress: impl FnMut(Progress),
+) -> anyhow::Result<T> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let task = tokio::task::spawn_blocking(move || op(&tx));
+
+    // The sender is dropped when `op` returns, which ends this loop
+    while let Some(stage) = rx.recv().await {
+        on_progress(stage);
     }
 
-    let stdout = String::from_utf8(output.stdout)?;
-    let txs: Vec<Tx> = serde_json::from_str(&stdout)
-        .map_err(|e| anyhow::anyhow!("Failed to parse CLI output: {}", e))?;
+    task.await
+        .map_err(|e| anyhow::anyhow!("Operation panicked: {}", e))?
+}
 
-    log::debug!("Prover generated {} transactions", txs.len());
-    Ok(txs)
+/// Wraps a prover to report when proving starts and finishes
+pub struct ReportingProver<'a> {
+    pub inner: &'a dyn Prover,
+    pub progress: &'a ProgressSender,
+}
+
+impl Prover for ReportingProver<'_> {
+    fn prove(
+        &self,
+        spell: &serde_json::Value,
+        prev_txs: &[String],
+        funding_utxo: &str,
+        funding_utxo_value: u64,
+        change_address: &str,
+        fee_rate: f64,
+    ) -> anyhow::Result<Vec<Tx>> {
+        report(Some(self.progress), Progress::ProvingStarted);
+        let txs = self.inner.prove(
+            spell,
+            prev_txs,
+            funding_utxo,
+            funding_utxo_value,
+            change_address,
+            fee_rate,
+        )?;
+        report(Some(self.progress), Progress::ProvingDone);
+        Ok(txs)
+    }
+
+    fn vk(&self) -> anyhow::Result<String> {
+        self.inner.vk()
+    }
 }
 
 // ============================================================================
 // NFT Creation
 // ============================================================================
 
-pub fn create_nft(btc: &Client, habit_name: String) -> anyhow::Result<String> {
+/// Check that an NFT destination is a supported address on the node's chain
+fn check_destination_address(btc: &Client, address: &str) -> anyhow::Result<()> {
+    validate_address_type(address)?;
+    let network = resolve_network(btc)?;
+    bitcoin::Address::from_str(address)?
+        .require_network(network)
+        .map_err(|_| anyhow::anyhow!("Destination {} is not a {} address", address, network))?;
+    Ok(())
+}
+
+pub fn create_nft(
+    btc: &Client,
+    prover: &dyn Prover,
+    habit_name: String,
+    options: CreateOptions,
+) -> anyhow::Result<String> {
+    let CreateOptions { to, meta, progress } = options;
+    let progress = progress.as_ref();
+
     println!("DEBUG: Starting create_nft for habit: '{}'", habit_name);
     log::debug!("Creating Habit Tracker NFT\n");
 
-    println!("DEBUG: Loading contract...");
-    let (vk, _binary_base64) = load_contract()?;
+    if let Some(to) = &to {
+        check_destination_address(btc, to)?;
+    }
+    if let Some(meta) = &meta {
+        validate_meta(meta)?;
+    }
 
-    println!("DEBUG: Getting funding UTXO...");
-    let (funding_utxo, funding_value, addr_str) = get_funding_utxo(btc, None)?;
+    println!("DEBUG: Loading contract...");
+    let vk = prover.vk()?;
 
     println!("DEBUG: Getting funding UTXO...");
-    log::debug!(
-        "Using funding UTXO: {} ({} sats)",
-        funding_utxo,
-        funding_value
-    );
-
-    println!("DEBUG: Generating app_id...");
-    let app_id = generate_app_id(&vk);
-    println!("DEBUG: Generating app_id...");
-
-    println!("DEBUG: Generating app_id...");
-    let spell = json!({
-        "version": 8,
-        "apps": {"$00": app_id},
-        "ins": [],
-        "outs": [{
-            "address": addr_str,
-            "charms": {
-                "$00": {
-                    "name": "🗡️ Habit Tracker",
-                    "description": format!("Tracking habit: {}", habit_name),
-                    "owner": addr_str,
-                    "habit_name": habit_name,
-                    "total_sessions": 0,
-                    "created_at": chrono::Utc::now().timestamp(),
-                }
-            },
-            "sats": NFT_AMOUNT_SATS
-        }]
-    });
-    println!("DEBUG: Spell created");
-
-    log::info!("\n Calling prover...");
-    println!("DEBUG: Getting contract path...");
-    let contract_path = get_contract_path();
-    println!("DEBUG: Getting contract path...");
-
-    println!("DEBUG: Calling prove_with_cli...");
-    let txs = prove_with_cli(
-        &spell,
-        contract_path.to_str().unwrap(),
-        &[],
-        &funding_utxo,
-        funding_value,
-        &addr_str,
-        DEFAULT_FEE_RATE,
-    )?;
-    println!("DEBUG: Prover returned {} transactions", txs.len());
+    let result = with_funding_retry(btc, &[], |funding_utxo, funding_value, addr_str| {
+        println!("DEBUG: Getting funding UTXO...");
+        log::debug!(
+            "Using funding UTXO: {} ({} sats)",
+            funding_utxo,
+            funding_value
+        );
+        ensure_wallet_owns_utxo(btc, &funding_utxo, "Funding")?;
+        ensure_wallet_owns_address(btc, &addr_str, "Change")?;
+
+        println!("DEBUG: Generating app_id...");
+        let app_id = compute_app_id(&identity_seed(&funding_utxo), &vk);
+        println!("DEBUG: Generating app_id...");
+
+        println!("DEBUG: Generating app_id...");
+        let owner = to.as_deref().unwrap_or(&addr_str);
+        let mut spell = json!({
+            "version": SPELL_VERSION,
+            "apps": {"$00": app_id},
+            "ins": [],
+            "outs": [{
+                "address": owner,
+                "charms": {
+                    "$00": {
+                        "name": "🗡️ Habit Tracker",
+                        "description": format!("Tracking habit: {}", habit_name),
+                        "owner": owner,
+                        "habit_name": habit_name,
+                        "total_sessions": 0,
+                        "created_at": chrono::Utc::now().timestamp(),
+                    }
+                },
+                "sats": NFT_AMOUNT_SATS
+            }]
+        });
+        attach_meta(&mut spell, meta.as_ref());
+        println!("DEBUG: Spell created");
+
+        let fee_rate = fee_rate()?;
+
+        ensure_funding_covers(&spell, 0, funding_value, fee_rate)?;
+
+        log::info!("\n Calling prover...");
+        println!("DEBUG: Calling prover...");
+        let txs = prover.prove(
+            &spell,
+            &[],
+            &funding_utxo,
+            funding_value,
+            &addr_str,
+            fee_rate,
+        )?;
+        println!("DEBUG: Prover returned {} transactions", txs.len());
 
-    log::info!(" Got transactions from prover");
+        log::info!(" Got transactions from prover");
 
-    let bitcoin_txs: Vec<bitcoin::Transaction> = txs
-        .iter()
-        .filter_map(|tx| match tx {
-            Tx::Bitcoin(btx) => Some(btx.inner().clone()),
-            _ => None,
-        })
-        .collect();
+        let (commit_tx, spell_tx) = classify_txs(&txs)?;
 
-    log::debug!(
-        "   Commit tx: {} bytes",
-        bitcoin::consensus::serialize(&bitcoin_txs[0]).len()
-    );
-    log::debug!(
-        "   Spell tx: {} bytes",
-        bitcoin::consensus::serialize(&bitcoin_txs[1]).len()
-    );
+        log::debug!(
+            "   Commit tx: {} bytes",
+            bitcoin::consensus::serialize(&commit_tx).len()
+        );
+        log::debug!(
+            "   Spell tx: {} bytes",
+            bitcoin::consensus::serialize(&spell_tx).len()
+        );
 
-    let result = sign_and_broadcast_create(btc, bitcoin_txs)?;
+        sign_and_broadcast_create(btc, &commit_tx, &spell_tx, progress)
+    })?;
 
     println!("DEBUG: Extracting spell txid...");
     let spell_txid = result
@@ -671,88 +1997,117 @@ pub fn create_nft(btc: &Client, habit_name: String) -> anyhow::Result<String> {
 //     Ok(())
 // }
 
-pub async fn update_nft(btc: &Client, nft_utxo: String) -> anyhow::Result<()> {
+pub fn update_nft(
+    btc: &Client,
+    prover: &dyn Prover,
+    nft_utxo: String,
+    options: UpdateOptions,
+) -> anyhow::Result<()> {
+    let UpdateOptions {
+        extra_outputs,
+        expected_sessions,
+        progress,
+    } = options;
+    let progress = progress.as_ref();
+
     println!("DEBUG: update_nft starting for UTXO: {}", &nft_utxo[..20]);
     log::info!("Updating NFT: {}", &nft_utxo[..12]);
-
-    println!("DEBUG: Getting funding UTXO...");
-    let (funding_utxo, funding_value, addr_str) = get_funding_utxo(btc, Some(&nft_utxo))?;
-    println!("DEBUG: Got funding UTXO: {}", &funding_utxo[..20]);
+    check_extra_outputs(btc, &extra_outputs)?;
 
     let (prev_txid, _) = nft_utxo
         .split_once(':')
         .ok_or_else(|| anyhow::anyhow!("Invalid UTXO format"))?;
 
     println!("DEBUG: Extracting NFT metadata...");
-    let (habit_name, current_sessions, _) = extract_nft_metadata(btc, prev_txid)?;
+    let metadata = ensure_habit_nft(btc, &nft_utxo)?;
+    let (habit_name, current_sessions, meta) =
+        (metadata.habit_name, metadata.total_sessions, metadata.meta);
     println!("DEBUG: Current sessions: {}", current_sessions);
+    check_expected_sessions(&nft_utxo, expected_sessions, current_sessions)?;
+
+    // The wallet signs the NFT input, so an NFT held elsewhere (e.g. minted
+    // with --to) has to go through update-unsigned instead
+    ensure_wallet_owns_utxo(btc, &nft_utxo, "NFT")?;
 
     println!("DEBUG: Getting previous transaction...");
-    let prev_tx_raw = btc.get_raw_transaction_hex(&bitcoin::Txid::from_str(prev_txid)?, None)?;
+    let prev_tx_raw = fetch_raw_tx_hex(btc, &bitcoin::Txid::from_str(prev_txid)?)?;
     println!("DEBUG: Got prev tx");
 
-    let (vk, _) = load_contract()?;
-    let app_id = generate_app_id(&vk);
+    // The NFT keeps whatever value it was minted with
+    let nft_sats = nft_output_sats(btc, &nft_utxo)?;
 
-    println!("DEBUG: Creating update spell...");
-    let spell = json!({
-        "version": 8,
-        "apps": {"$00": app_id},
-        "ins": [{
-            "utxo_id": nft_utxo.clone(),
-            "charms": {
-                "$00": {
-                    "name": "🗡️ Habit Tracker",
-                    "description": format!("Tracking habit: {}", habit_name),
-                    "owner": addr_str,
-                    "habit_name": habit_name.clone(),
-                    "total_sessions": current_sessions,
-                    "badges": get_badges_for_sessions(current_sessions),
-                }
-            }
-        }],
-        "outs": [{
-            "address": addr_str,
-            "charms": {
-                "$00": {
-                    "name": "🗡️ Habit Tracker",
-                    "description": format!("Tracking habit: {}", habit_name),
-                    "owner": addr_str,
-                    "habit_name": habit_name,
-                    "total_sessions": current_sessions + 1,
-                    "last_updated": chrono::Utc::now().timestamp(),
-                    "badges": get_badges_for_sessions(current_sessions + 1),
-                }
-            },
-            "sats": NFT_AMOUNT_SATS
-        }]
-    });
+    // The app id is the NFT's identity for its whole life; a fresh one here
+    // would mint a new NFT instead of updating this one
+    let app_id = metadata.app_id;
 
-    println!("DEBUG: Calling prover...");
-    let contract_path = get_contract_path();
-    let txs = prove_with_cli(
-        &spell,
-        contract_path.to_str().unwrap(),
-        &[prev_tx_raw],
-        &funding_utxo,
-        funding_value,
-        &addr_str,
-        DEFAULT_FEE_RATE,
+    println!("DEBUG: Getting funding UTXO...");
+    let result = with_funding_retry(
+        btc,
+        &[&nft_utxo],
+        |funding_utxo, funding_value, addr_str| {
+            ensure_wallet_owns_utxo(btc, &funding_utxo, "Funding")?;
+            ensure_wallet_owns_address(btc, &addr_str, "Change")?;
+
+            println!("DEBUG: Creating update spell...");
+            let mut spell = json!({
+                "version": SPELL_VERSION,
+                "apps": {"$00": app_id},
+                "ins": [{
+                    "utxo_id": nft_utxo.clone(),
+                    "charms": {
+                        "$00": {
+                            "name": "🗡️ Habit Tracker",
+                            "description": format!("Tracking habit: {}", habit_name),
+                            "owner": addr_str,
+                            "habit_name": habit_name.clone(),
+                            "total_sessions": current_sessions,
+                            "badges": get_badges_for_sessions(current_sessions),
+                        }
+                    }
+                }],
+                "outs": [{
+                    "address": addr_str,
+                    "charms": {
+                        "$00": {
+                            "name": "🗡️ Habit Tracker",
+                            "description": format!("Tracking habit: {}", habit_name),
+                            "owner": addr_str,
+                            "habit_name": habit_name,
+                            "total_sessions": current_sessions + 1,
+                            "last_updated": chrono::Utc::now().timestamp(),
+                            "nonce": spell_nonce(),
+                            "badges": get_badges_for_sessions(current_sessions + 1),
+                        }
+                    },
+                    "sats": nft_sats
+                }]
+            });
+            attach_meta(&mut spell, meta.as_ref());
+            push_extra_outputs(&mut spell, extra_outputs.clone());
+
+            let fee_rate = fee_rate()?;
+
+            ensure_funding_covers(&spell, nft_sats, funding_value, fee_rate)?;
+
+            println!("DEBUG: Calling prover...");
+            let txs = prover.prove(
+                &spell,
+                std::slice::from_ref(&prev_tx_raw),
+                &funding_utxo,
+                funding_value,
+                &addr_str,
+                fee_rate,
+            )?;
+            println!("DEBUG: Prover returned {} txs", txs.len());
+
+            println!("DEBUG: Converting to bitcoin transactions...");
+            let (commit_tx, spell_tx) = classify_txs(&txs)?;
+
+            println!("DEBUG: Signing and broadcasting...");
+
+            sign_and_broadcast_update(btc, &commit_tx, &spell_tx, prev_txid, &nft_utxo, progress)
+        },
     )?;
-    println!("DEBUG: Prover returned {} txs", txs.len());
-
-    println!("DEBUG: Converting to bitcoin transactions...");
-    let bitcoin_txs: Vec<bitcoin::Transaction> = txs
-        .iter()
-        .filter_map(|tx| match tx {
-            Tx::Bitcoin(btx) => Some(btx.inner().clone()),
-            _ => None,
-        })
-        .collect();
-    println!("DEBUG: Converted to {} bitcoin txs", bitcoin_txs.len());
-
-    println!("DEBUG: Signing and broadcasting...");
-    let result = sign_and_broadcast_update(btc, bitcoin_txs, prev_txid, &nft_utxo)?;
     println!("DEBUG: Broadcast complete");
 
     if let Some(spell_txid) = result
@@ -801,42 +2156,60 @@ pub async fn update_nft(btc: &Client, nft_utxo: String) -> anyhow::Result<()> {
 
 pub fn update_nft_unsigned(
     btc: &Client,
+    prover: &dyn Prover,
     nft_utxo: String,
     user_address: String,
     funding_utxo: String,
     funding_value: u64,
+    options: UpdateOptions,
 ) -> anyhow::Result<UnsignedUpdateResponse> {
+    let UpdateOptions {
+        extra_outputs,
+        expected_sessions,
+        ..
+    } = options;
+
     log::info!("Building unsigned NFT creation transactions");
 
-    let (vk, _binary_base64) = load_contract()?;
+    validate_address_type(&user_address)?;
 
     log::debug!(" User address: {}", user_address);
     log::debug!(" Funding UTXO: {} ({} sats)", funding_utxo, funding_value);
     log::debug!(" NFT UTXO: {}", nft_utxo);
 
-    if funding_value < MIN_FUNDING_SATS {
+    // Extra payments are funded on top of the NFT output and fees
+    let extra_sats: u64 = extra_outputs.iter().map(|(_, sats)| sats).sum();
+    let min_required = MIN_FUNDING_SATS + extra_sats;
+    if funding_value < min_required {
         anyhow::bail!(
             "Insufficient funds. Have {} sats, need at least {} sats",
             funding_value,
-            MIN_FUNDING_SATS
+            min_required
         );
     }
 
+    check_extra_outputs(btc, &extra_outputs)?;
+
     // Extract current metadata
     let parts: Vec<&str> = nft_utxo.split(':').collect();
     let prev_txid = parts[0];
 
-    let (habit_name, current_sessions, _) = extract_nft_metadata(btc, prev_txid)?;
+    let metadata = ensure_habit_nft(btc, &nft_utxo)?;
+    let (habit_name, current_sessions, meta) =
+        (metadata.habit_name, metadata.total_sessions, metadata.meta);
+
+    check_expected_sessions(&nft_utxo, expected_sessions, current_sessions)?;
 
     println!(" Current state: {} sessions", current_sessions);
     println!("  New state: {} sessions", current_sessions + 1);
 
     // Get previous transaction hex using the client
-    let prev_tx_raw = btc.get_raw_transaction_hex(&bitcoin::Txid::from_str(prev_txid)?, None)?;
-    let app_id = generate_app_id(&vk);
+    let prev_tx_raw = fetch_raw_tx_hex(btc, &bitcoin::Txid::from_str(prev_txid)?)?;
+    let nft_sats = nft_output_sats(btc, &nft_utxo)?;
+    let app_id = metadata.app_id;
 
-    let spell = json!({
-        "version": 8,
+    let mut spell = json!({
+        "version": SPELL_VERSION,
         "apps": {"$00": app_id},
         "ins": [{
             "utxo_id": nft_utxo,
@@ -861,78 +2234,494 @@ pub fn update_nft_unsigned(
                     "habit_name": habit_name,
                     "total_sessions": current_sessions + 1,
                     "last_updated": chrono::Utc::now().timestamp(),
+                    "nonce": spell_nonce(),
                     "badges": get_badges_for_sessions(current_sessions + 1),
                 }
             },
-            "sats": NFT_AMOUNT_SATS
+            "sats": nft_sats
         }]
     });
 
-    log::debug!("\n🔮 Calling prover...");
+    attach_meta(&mut spell, meta.as_ref());
+
+    push_extra_outputs(&mut spell, extra_outputs);
+
+    let (commit_tx, spell_tx, signing_info) = prove_unsigned_transition(
+        prover,
+        &spell,
+        prev_tx_raw,
+        (&funding_utxo, funding_value),
+        &user_address,
+        nft_sats,
+    )?;
+
+    Ok(UnsignedUpdateResponse {
+        commit_tx_hex: hex::encode(bitcoin::consensus::serialize(&commit_tx)),
+        spell_tx_hex: hex::encode(bitcoin::consensus::serialize(&spell_tx)),
+        commit_txid: commit_tx.compute_txid().to_string(),
+        spell_inputs_info: signing_info,
+        current_sessions,
+        new_sessions: current_sessions + 1,
+        predicted_nft_utxo: predicted_nft_utxo(&spell_tx),
+    })
+}
+
+/// Smallest amount an output to `address` can carry without being dust at
+/// the default relay policy (294 sats for P2WPKH, 330 for P2TR, 546 for P2PKH)
+pub fn dust_limit(address: &str) -> anyhow::Result<u64> {
+    Ok(bitcoin::Address::from_str(address)?
+        .assume_checked()
+        .script_pubkey()
+        .minimal_non_dust()
+        .to_sat())
+}
+
+/// Check extra payment outputs: valid addresses on the node's network, each
+/// carrying at least the dust limit for its address type
+fn check_extra_outputs(btc: &Client, extra_outputs: &[(String, u64)]) -> anyhow::Result<()> {
+    let network = resolve_network(btc)?;
+    for (address, sats) in extra_outputs {
+        bitcoin::Address::from_str(address)
+            .and_then(|addr| addr.require_network(network))
+            .map_err(|e| anyhow::anyhow!("Invalid extra output address {}: {}", address, e))?;
+        let dust = dust_limit(address)?;
+        if *sats < dust {
+            anyhow::bail!(
+                "Extra output to {} carries {} sats, below the {} sat dust limit",
+                address,
+                sats,
+                dust
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The input charm is rebuilt from chain state, so a caller working from an
+/// older view (or replaying an old update) is caught here
+fn check_expected_sessions(
+    nft_utxo: &str,
+    expected_sessions: Option<u64>,
+    current_sessions: u64,
+) -> anyhow::Result<()> {
+    if let Some(expected) = expected_sessions {
+        if expected != current_sessions {
+            anyhow::bail!(
+                "Stale update: NFT {} has {} sessions on chain, request expected {}",
+                nft_utxo,
+                current_sessions,
+                expected
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Plain payment outputs follow the NFT output; they carry no charms so the
+/// contract never looks at them
+fn push_extra_outputs(spell: &mut serde_json::Value, extra_outputs: Vec<(String, u64)>) {
+    if let Some(outs) = spell["outs"].as_array_mut() {
+        for (address, sats) in extra_outputs {
+            outs.push(json!({"address": address, "sats": sats}));
+        }
+    }
+}
+
+/// Prove a spell that spends an NFT and describe the inputs the caller has
+/// to sign. Shared by session updates and renames.
+fn prove_unsigned_transition(
+    prover: &dyn Prover,
+    spell: &serde_json::Value,
+    prev_tx_raw: String,
+    (funding_utxo, funding_value): (&str, u64),
+    user_address: &str,
+    nft_sats: u64,
+) -> anyhow::Result<(
+    bitcoin::Transaction,
+    bitcoin::Transaction,
+    Vec<SigningInputInfo>,
+)> {
+    log::debug!("\n🔮 Calling prover...");
+
+    let fee_rate = fee_rate()?;
+
+    ensure_funding_covers(spell, nft_sats, funding_value, fee_rate)?;
+
+    let prev_txs = vec![prev_tx_raw];
+
+    let txs = prover.prove(
+        spell,
+        &prev_txs,
+        funding_utxo,
+        funding_value,
+        user_address,
+        fee_rate,
+    )?;
+
+    log::debug!("   ✓ Got transactions from prover");
+
+    let (commit_tx, spell_tx) = classify_txs(&txs)?;
+
+    // Extract signing info
+    let signing_info = vec![
+        // Commit tx - needs funding UTXO script
+        SigningInputInfo {
+            tx_index: 0,
+            input_index: 0,
+            prev_script_hex: "".to_string(),
+            amount_sats: funding_value,
+        },
+        // Spell tx has 2 inputs: NFT UTXO + commit output
+        // Input 0: NFT UTXO
+        SigningInputInfo {
+            tx_index: 1,
+            input_index: 0,
+            prev_script_hex: "".to_string(),
+            amount_sats: nft_sats,
+        },
+        // Input 1: Commit output
+        SigningInputInfo {
+            tx_index: 1,
+            input_index: 1,
+            prev_script_hex: hex::encode(commit_tx.output[0].script_pubkey.as_bytes()),
+            amount_sats: commit_tx.output[0].value.to_sat(),
+        },
+    ];
+
+    Ok((commit_tx, spell_tx, signing_info))
+}
+
+// ============================================================================
+// Renaming
+// ============================================================================
+
+/// Longest habit name a rename accepts, in characters
+pub const MAX_HABIT_NAME_CHARS: usize = 64;
+
+/// Check a new habit name: non-empty and at most MAX_HABIT_NAME_CHARS
+pub fn validate_habit_name(name: &str) -> anyhow::Result<()> {
+    if name.trim().is_empty() {
+        anyhow::bail!("Habit name must not be empty");
+    }
+    if name.chars().count() > MAX_HABIT_NAME_CHARS {
+        anyhow::bail!(
+            "Habit name is {} characters, at most {} allowed",
+            name.chars().count(),
+            MAX_HABIT_NAME_CHARS
+        );
+    }
+    Ok(())
+}
+
+/// Build unsigned transactions that change a habit's name while keeping its
+/// sessions, badges and update clock. The contract accepts this as a rename,
+/// separate from a session increment.
+pub fn rename_nft_unsigned(
+    btc: &Client,
+    prover: &dyn Prover,
+    nft_utxo: String,
+    new_name: String,
+    user_address: String,
+    funding_utxo: String,
+    funding_value: u64,
+) -> anyhow::Result<UnsignedUpdateResponse> {
+    validate_habit_name(&new_name)?;
+    validate_address_type(&user_address)?;
+
+    if funding_value < MIN_FUNDING_SATS {
+        anyhow::bail!(
+            "Insufficient funds. Have {} sats, need at least {} sats",
+            funding_value,
+            MIN_FUNDING_SATS
+        );
+    }
+
+    let metadata = ensure_habit_nft(btc, &nft_utxo)?;
+    if metadata.habit_name == new_name {
+        anyhow::bail!("Habit is already named '{}'", new_name);
+    }
+    // The contract never lets the owner change, renames included
+    if metadata.owner != user_address {
+        anyhow::bail!(
+            "NFT {} is owned by {}, not {}",
+            nft_utxo,
+            metadata.owner,
+            user_address
+        );
+    }
+    let sessions = metadata.total_sessions;
+
+    let (prev_txid, _) = parse_utxo(&nft_utxo)?;
+    let prev_tx_raw = fetch_raw_tx_hex(btc, &prev_txid)?;
+    let nft_sats = nft_output_sats(btc, &nft_utxo)?;
+
+    // Start from the charm as it is on chain so sessions, badges, meta and
+    // the update clock all carry over; only the name changes
+    let input_charm =
+        decode_spell(btc, &prev_txid.to_string())?["outs"][0]["charms"]["$0000"].clone();
+    let mut output_charm = input_charm.clone();
+    output_charm["habit_name"] = json!(new_name);
+    output_charm["description"] = json!(format!("Tracking habit: {}", new_name));
+
+    log::info!(
+        "Renaming '{}' → '{}' ({} sessions)",
+        metadata.habit_name,
+        new_name,
+        sessions
+    );
+
+    let spell = json!({
+        "version": SPELL_VERSION,
+        "apps": {"$00": metadata.app_id},
+        "ins": [{
+            "utxo_id": nft_utxo,
+            "charms": {"$00": input_charm}
+        }],
+        "outs": [{
+            "address": user_address,
+            "charms": {"$00": output_charm},
+            "sats": nft_sats
+        }]
+    });
+
+    let (commit_tx, spell_tx, signing_info) = prove_unsigned_transition(
+        prover,
+        &spell,
+        prev_tx_raw,
+        (&funding_utxo, funding_value),
+        &user_address,
+        nft_sats,
+    )?;
+
+    Ok(UnsignedUpdateResponse {
+        commit_tx_hex: hex::encode(bitcoin::consensus::serialize(&commit_tx)),
+        spell_tx_hex: hex::encode(bitcoin::consensus::serialize(&spell_tx)),
+        commit_txid: commit_tx.compute_txid().to_string(),
+        spell_inputs_info: signing_info,
+        current_sessions: sessions,
+        new_sessions: sessions,
+        predicted_nft_utxo: predicted_nft_utxo(&spell_tx),
+    })
+}
+
+/// Rename an NFT held by the wallet, funding, signing and broadcasting it
+pub fn rename_nft(
+    btc: &Client,
+    prover: &dyn Prover,
+    nft_utxo: String,
+    new_name: String,
+) -> anyhow::Result<()> {
+    ensure_wallet_owns_utxo(btc, &nft_utxo, "NFT")?;
+    let owner = ensure_habit_nft(btc, &nft_utxo)?.owner;
+    let (prev_txid, _) = parse_utxo(&nft_utxo)?;
+
+    let result = with_funding_retry(btc, &[&nft_utxo], |funding_utxo, funding_value, _| {
+        let unsigned = rename_nft_unsigned(
+            btc,
+            prover,
+            nft_utxo.clone(),
+            new_name.clone(),
+            owner.clone(),
+            funding_utxo,
+            funding_value,
+        )?;
+        let commit_tx: bitcoin::Transaction =
+            bitcoin::consensus::deserialize(&hex::decode(&unsigned.commit_tx_hex)?)?;
+        let spell_tx: bitcoin::Transaction =
+            bitcoin::consensus::deserialize(&hex::decode(&unsigned.spell_tx_hex)?)?;
+        sign_and_broadcast_update(
+            btc,
+            &commit_tx,
+            &spell_tx,
+            &prev_txid.to_string(),
+            &nft_utxo,
+            None,
+        )
+    })?;
 
-    let contract_path = get_contract_path();
+    let spell_txid = result["tx-results"][1]["txid"].as_str().unwrap_or_default();
+    println!("✓ Renamed to '{}'", new_name);
+    println!("   New UTXO: {}:0", spell_txid);
+    Ok(())
+}
 
-    let prev_txs = vec![prev_tx_raw];
+pub fn locate_nft(btc: &Client, app_id: String) -> anyhow::Result<()> {
+    match find_current_utxo_by_app_id(btc, &app_id)? {
+        Some(utxo) => {
+            println!("\n📍 NFT FOUND");
+            println!("   App ID: {}", app_id);
+            println!("   UTXO: {}", utxo);
+            println!("\nTo view it:");
+            println!("   cargo run -- view --utxo {}\n", utxo);
+            Ok(())
+        }
+        None => anyhow::bail!("No unspent NFT with app id {} in this wallet", app_id),
+    }
+}
 
-    let txs = prove_with_cli(
-        &spell,
-        contract_path.to_str().unwrap(),
-        &prev_txs,
-        &funding_utxo,
-        funding_value,
-        &user_address,
-        DEFAULT_FEE_RATE,
-    )?;
+/// Unconfirmed transaction spending `nft_utxo`, if any. Relies on
+/// `gettxspendingprevout` (Bitcoin Core 24+); older nodes report nothing.
+fn mempool_spender(btc: &Client, nft_utxo: &str) -> anyhow::Result<Option<bitcoin::Txid>> {
+    let (txid, vout) = parse_utxo(nft_utxo)?;
+    if btc.get_tx_out(&txid, vout, Some(true))?.is_some() {
+        return Ok(None);
+    }
 
-    log::debug!("   ✓ Got transactions from prover");
+    let spending: serde_json::Value = match btc.call(
+        "gettxspendingprevout",
+        &[json!([{"txid": txid.to_string(), "vout": vout}])],
+    ) {
+        Ok(spending) => spending,
+        Err(e) => {
+            log::debug!("gettxspendingprevout unavailable: {}", e);
+            return Ok(None);
+        }
+    };
+    spending
+        .pointer("/0/spendingtxid")
+        .and_then(|v| v.as_str())
+        .map(bitcoin::Txid::from_str)
+        .transpose()
+        .map_err(Into::into)
+}
 
-    let bitcoin_txs: Vec<bitcoin::Transaction> = txs
-        .iter()
-        .filter_map(|tx| match tx {
-            Tx::Bitcoin(btx) => Some(btx.inner().clone()),
-            _ => None,
-        })
-        .collect();
+/// NFT state including unconfirmed updates: if a mempool transaction has
+/// already spent `nft_utxo`, the state from its spell and the UTXO the NFT
+/// moved to; otherwise the state at `nft_utxo` and None
+pub fn fetch_nft_state(
+    btc: &Client,
+    nft_utxo: &str,
+) -> anyhow::Result<(NftMetadata, Option<String>)> {
+    if let Some(spender) = mempool_spender(btc, nft_utxo)? {
+        match fetch_nft_metadata(btc, &spender.to_string()) {
+            Ok(metadata) => return Ok((metadata, Some(format!("{}:0", spender)))),
+            Err(e) => log::warn!(
+                "{} spent by {}, not a habit spell: {:#}",
+                nft_utxo,
+                spender,
+                e
+            ),
+        }
+    }
 
-    let commit_tx = &bitcoin_txs[0];
-    let spell_tx = &bitcoin_txs[1];
+    let (txid, _) = parse_utxo(nft_utxo)?;
+    Ok((fetch_nft_metadata(btc, &txid.to_string())?, None))
+}
 
-    // Extract signing info
-    let signing_info = vec![
-        // Commit tx - needs funding UTXO script
-        SigningInputInfo {
-            tx_index: 0,
-            input_index: 0,
-            prev_script_hex: "".to_string(),
-            amount_sats: funding_value,
-        },
-        // Spell tx has 2 inputs: NFT UTXO + commit output
-        // Input 0: NFT UTXO
-        SigningInputInfo {
-            tx_index: 1,
-            input_index: 0,
-            prev_script_hex: "".to_string(),
-            amount_sats: 1000,
-        },
-        // Input 1: Commit output
-        SigningInputInfo {
-            tx_index: 1,
-            input_index: 1,
-            prev_script_hex: hex::encode(commit_tx.output[0].script_pubkey.as_bytes()),
-            amount_sats: commit_tx.output[0].value.to_sat(),
-        },
-    ];
+/// Fees paid over an NFT's life, from its mint up to a given UTXO
+#[derive(Serialize, Debug)]
+pub struct LifetimeCost {
+    pub total_sessions: u64,
+    /// Commit and spell transactions walked
+    pub transactions: usize,
+    /// Spell transactions walked: the mint, every update and every rename
+    pub spells: usize,
+    pub total_fees_sats: u64,
+    /// Total fees averaged over `spells`
+    pub avg_fee_per_session: f64,
+}
 
-    Ok(UnsignedUpdateResponse {
-        commit_tx_hex: hex::encode(bitcoin::consensus::serialize(commit_tx)),
-        spell_tx_hex: hex::encode(bitcoin::consensus::serialize(spell_tx)),
-        commit_txid: commit_tx.compute_txid().to_string(),
-        spell_inputs_info: signing_info,
-        current_sessions,
-        new_sessions: current_sessions + 1,
+/// Fee paid by a transaction: the mempool's figure while unconfirmed,
+/// otherwise inputs minus outputs
+fn tx_fee(btc: &Client, tx: &bitcoin::Transaction) -> anyhow::Result<u64> {
+    if let Ok(entry) = btc.get_mempool_entry(&tx.compute_txid()) {
+        return Ok(entry.fees.base.to_sat());
+    }
+
+    let mut in_sats = 0;
+    for input in &tx.input {
+        let prevout = &input.previous_output;
+        let prev_tx: bitcoin::Transaction =
+            bitcoin::consensus::deserialize(&hex::decode(fetch_raw_tx_hex(btc, &prevout.txid)?)?)?;
+        in_sats += prev_tx
+            .output
+            .get(prevout.vout as usize)
+            .ok_or_else(|| anyhow::anyhow!("{} has no output {}", prevout.txid, prevout.vout))?
+            .value
+            .to_sat();
+    }
+    let out_sats: u64 = tx.output.iter().map(|out| out.value.to_sat()).sum();
+    in_sats
+        .checked_sub(out_sats)
+        .ok_or_else(|| anyhow::anyhow!("{} spends more than it takes in", tx.compute_txid()))
+}
+
+/// Walk an NFT back to its mint through each spell's NFT input, summing the
+/// fees of every commit and spell transaction along the way
+pub fn lifetime_cost(btc: &Client, nft_utxo: &str) -> anyhow::Result<LifetimeCost> {
+    let total_sessions = ensure_habit_nft(btc, nft_utxo)?.total_sessions;
+
+    let (mut txid, _) = parse_utxo(nft_utxo)?;
+    let mut transactions = 0;
+    let mut spells = 0;
+    let mut total_fees_sats = 0;
+    loop {
+        let tx_hex = fetch_raw_tx_hex(btc, &txid)?;
+        let spell_tx: bitcoin::Transaction =
+            bitcoin::consensus::deserialize(&hex::decode(&tx_hex)?)?;
+        let spell = decode_spell_hex(&tx_hex)?;
+
+        // The previous NFT, if any; every other input comes from the commit tx
+        let prev_nft = spell
+            .pointer("/ins/0/utxo_id")
+            .and_then(|v| v.as_str())
+            .map(parse_utxo)
+            .transpose()?;
+        let commit_txids: std::collections::BTreeSet<_> = spell_tx
+            .input
+            .iter()
+            .map(|input| input.previous_output)
+            .filter(|outpoint| {
+                prev_nft.is_none_or(|(prev_txid, prev_vout)| {
+                    (outpoint.txid, outpoint.vout) != (prev_txid, prev_vout)
+                })
+            })
+            .map(|outpoint| outpoint.txid)
+            .collect();
+
+        total_fees_sats += tx_fee(btc, &spell_tx)?;
+        transactions += 1;
+        spells += 1;
+        for commit_txid in commit_txids {
+            let commit_tx: bitcoin::Transaction = bitcoin::consensus::deserialize(&hex::decode(
+                fetch_raw_tx_hex(btc, &commit_txid)?,
+            )?)?;
+            total_fees_sats += tx_fee(btc, &commit_tx)?;
+            transactions += 1;
+        }
+
+        match prev_nft {
+            Some((prev_txid, _)) => txid = prev_txid,
+            None => break,
+        }
+    }
+
+    Ok(LifetimeCost {
+        total_sessions,
+        transactions,
+        spells,
+        total_fees_sats,
+        avg_fee_per_session: total_fees_sats as f64 / spells as f64,
     })
 }
 
+/// Print what an NFT has cost in fees so far
+pub fn print_cost(btc: &Client, nft_utxo: String) -> anyhow::Result<()> {
+    let cost = lifetime_cost(btc, &nft_utxo)?;
+
+    println!("\n💰 Lifetime cost of {}", nft_utxo);
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    println!("   Sessions: {}", cost.total_sessions);
+    println!("   Transactions: {}", cost.transactions);
+    println!("   Total fees: {} sats", cost.total_fees_sats);
+    println!(
+        "   Average per session: {:.0} sats (over {} spells, mint included)",
+        cost.avg_fee_per_session, cost.spells
+    );
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    Ok(())
+}
+
 pub fn view_nft(btc: &Client, nft_utxo: String) -> anyhow::Result<()> {
     log::info!("Viewing NFT: {}", &nft_utxo[..12]);
 
@@ -940,7 +2729,12 @@ pub fn view_nft(btc: &Client, nft_utxo: String) -> anyhow::Result<()> {
         .split_once(':')
         .ok_or_else(|| anyhow::anyhow!("Invalid UTXO format"))?;
 
-    let (habit_name, sessions, owner) = extract_nft_metadata(btc, txid)?;
+    let (metadata, pending_utxo) = fetch_nft_state(btc, &nft_utxo)?;
+    let (habit_name, sessions, owner) = (
+        &metadata.habit_name,
+        metadata.total_sessions,
+        &metadata.owner,
+    );
 
     // Determine which stage the user is in
     let stage = if sessions < 23 {
@@ -960,6 +2754,27 @@ pub fn view_nft(btc: &Client, nft_utxo: String) -> anyhow::Result<()> {
     println!("   Stage: {}", stage);
     println!("   Owner: {}...", &owner[..20]);
     println!("   UTXO: {}:{}", txid, vout);
+    if let Some(pending) = &pending_utxo {
+        println!("   ⏳ Pending: moved to {} (unconfirmed)", pending);
+    }
+    println!("   App ID: {}", metadata.app_id);
+    if let Some(meta) = &metadata.meta {
+        let mut keys: Vec<_> = meta.keys().collect();
+        keys.sort();
+        for key in keys {
+            println!("   Meta {}: {}", key, meta[key]);
+        }
+    }
+    match local_contract_vk() {
+        Some(local_vk) if local_vk == metadata.vk => {
+            println!("   Contract VK: {} (matches local build)", metadata.vk)
+        }
+        Some(_) => println!(
+            "   Contract VK: {} (⚠️  differs from local build)",
+            metadata.vk
+        ),
+        None => println!("   Contract VK: {}", metadata.vk),
+    }
 
     // Progress bar
     let progress = if sessions <= 66 {
@@ -1011,15 +2826,27 @@ pub fn view_nft(btc: &Client, nft_utxo: String) -> anyhow::Result<()> {
 
 // Function 1: Build unsigned transactions
 pub fn create_nft_unsigned(
+    prover: &dyn Prover,
     habit_name: String,
     user_address: String,
     funding_utxo: String,
     funding_value: u64,
+    options: CreateOptions,
 ) -> anyhow::Result<UnsignedNftResponse> {
+    let CreateOptions { to, meta, .. } = options;
+
     log::debug!("🗡️  Building unsigned NFT transactions\n");
 
+    validate_address_type(&user_address)?;
+    if let Some(to) = &to {
+        validate_address_type(to)?;
+    }
+    if let Some(meta) = &meta {
+        validate_meta(meta)?;
+    }
+
     // No need for btc client here - we're not signing or broadcasting
-    let (vk, _binary_base64) = load_contract()?;
+    let vk = prover.vk()?;
 
     log::debug!(" User address: {}", user_address);
     log::debug!(" Funding UTXO: {} ({} sats)", funding_utxo, funding_value);
@@ -1034,24 +2861,20 @@ pub fn create_nft_unsigned(
         );
     }
 
-    let identity_input = format!("habit_tracker_{}", chrono::Utc::now().timestamp());
-    let mut hasher = Sha256::new();
-    hasher.update(identity_input.as_bytes());
-    let identity_hash = hasher.finalize();
-    let identity_hex = hex::encode(identity_hash);
-    let app_id = format!("n/{}/{}", identity_hex, vk);
+    let app_id = compute_app_id(&identity_seed(&funding_utxo), &vk);
+    let owner = to.as_deref().unwrap_or(&user_address);
 
-    let spell = json!({
-        "version": 8,
+    let mut spell = json!({
+        "version": SPELL_VERSION,
         "apps": {"$00": app_id},
         "ins": [],
         "outs": [{
-            "address": user_address,
+            "address": owner,
             "charms": {
                 "$00": {
                     "name": "🗡️ Habit Tracker",
                     "description": format!("Tracking habit: {}", habit_name),
-                    "owner": user_address,
+                    "owner": owner,
                     "habit_name": habit_name,
                     "total_sessions": 0,
                     "created_at": chrono::Utc::now().timestamp(),
@@ -1061,34 +2884,27 @@ pub fn create_nft_unsigned(
             "sats": NFT_AMOUNT_SATS
         }]
     });
+    attach_meta(&mut spell, meta.as_ref());
 
-    log::debug!("\n Calling prover...");
+    let fee_rate = fee_rate()?;
 
-    let contract_path = get_contract_path();
+    ensure_funding_covers(&spell, 0, funding_value, fee_rate)?;
 
-    let txs = prove_with_cli(
+    log::debug!("\n Calling prover...");
+
+    let txs = prover.prove(
         &spell,
-        contract_path.to_str().unwrap(),
         &[],
         &funding_utxo,
         funding_value,
         &user_address,
-        DEFAULT_FEE_RATE,
+        fee_rate,
     )?;
 
     log::debug!("   ✓ Got transactions from prover");
 
     // Convert to bitcoin::Transaction objects
-    let bitcoin_txs: Vec<bitcoin::Transaction> = txs
-        .iter()
-        .filter_map(|tx| match tx {
-            Tx::Bitcoin(btx) => Some(btx.inner().clone()),
-            _ => None,
-        })
-        .collect();
-
-    let commit_tx = &bitcoin_txs[0];
-    let spell_tx = &bitcoin_txs[1];
+    let (commit_tx, spell_tx) = &classify_txs(&txs)?;
 
     // Extract signing info
     let signing_info = vec![
@@ -1113,9 +2929,103 @@ pub fn create_nft_unsigned(
         spell_tx_hex: hex::encode(bitcoin::consensus::serialize(spell_tx)),
         commit_txid: commit_tx.compute_txid().to_string(),
         spell_inputs_info: signing_info,
+        predicted_nft_utxo: predicted_nft_utxo(spell_tx),
     })
 }
 
+/// Build unsigned creates for several habits, each paid by its own funding
+/// UTXO. Habits fail independently; the only up-front failure is not having
+/// a distinct funding UTXO for every habit.
+pub fn create_many_unsigned(
+    prover: &dyn Prover,
+    habits: Vec<String>,
+    user_address: &str,
+    funding_utxos: Vec<(String, u64)>,
+) -> anyhow::Result<Vec<BatchCreateResult>> {
+    if funding_utxos.len() < habits.len() {
+        anyhow::bail!(
+            "{} habits need {} funding UTXOs, got {}",
+            habits.len(),
+            habits.len(),
+            funding_utxos.len()
+        );
+    }
+    let distinct: std::collections::HashSet<_> = funding_utxos.iter().map(|(u, _)| u).collect();
+    if distinct.len() != funding_utxos.len() {
+        anyhow::bail!("Funding UTXOs must be distinct");
+    }
+
+    Ok(habits
+        .into_iter()
+        .zip(funding_utxos)
+        .map(|(habit, (funding_utxo, funding_value))| {
+            match create_nft_unsigned(
+                prover,
+                habit.clone(),
+                user_address.to_string(),
+                funding_utxo,
+                funding_value,
+                CreateOptions::default(),
+            ) {
+                Ok(unsigned) => BatchCreateResult {
+                    habit,
+                    unsigned: Some(unsigned),
+                    error: None,
+                },
+                Err(e) => {
+                    log::warn!("Bulk create of '{}' failed: {:#}", habit, e);
+                    BatchCreateResult {
+                        habit,
+                        unsigned: None,
+                        error: Some(format!("{:#}", e)),
+                    }
+                }
+            }
+        })
+        .collect())
+}
+
+/// Mint several habits from the node wallet, reporting each one
+pub fn create_many(btc: &Client, prover: &dyn Prover, habits: Vec<String>) -> anyhow::Result<()> {
+    let funding_utxos = select_funding_utxos(btc, habits.len())?;
+    let user_address = btc
+        .get_new_address(None, None)?
+        .require_network(resolve_network(btc)?)?
+        .to_string();
+
+    // The wallet signs every create, so check up front it can, as create_nft does
+    for (funding_utxo, _) in &funding_utxos {
+        ensure_wallet_owns_utxo(btc, funding_utxo, "Funding")?;
+    }
+    ensure_wallet_owns_address(btc, &user_address, "Change")?;
+
+    let total = habits.len();
+    let mut failed = 0;
+    for result in create_many_unsigned(prover, habits, &user_address, funding_utxos)? {
+        let minted = match result.unsigned {
+            Some(unsigned) => [unsigned.commit_tx_hex, unsigned.spell_tx_hex]
+                .iter()
+                .map(|tx_hex| Ok(bitcoin::consensus::deserialize(&hex::decode(tx_hex)?)?))
+                .collect::<anyhow::Result<Vec<bitcoin::Transaction>>>()
+                .and_then(|txs| sign_and_broadcast_create(btc, &txs[0], &txs[1], None)),
+            None => Err(anyhow::anyhow!("{}", result.error.unwrap_or_default())),
+        };
+
+        match minted {
+            Ok(_) => println!("✓ {}", result.habit),
+            Err(e) => {
+                failed += 1;
+                println!("✗ {}: {:#}", result.habit, e);
+            }
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} of {} habits failed", failed, total);
+    }
+    Ok(())
+}
+
 // Function 2: Broadcast signed transactions
 pub fn broadcast_nft(
     btc: &Client,
@@ -1131,13 +3041,23 @@ pub fn broadcast_nft(
     let spell_bytes = hex::decode(&signed_spell_hex)?;
     let spell_tx: bitcoin::Transaction = bitcoin::consensus::deserialize(&spell_bytes)?;
 
-    // Broadcast commit first
-    let commit_txid = btc.send_raw_transaction(&commit_tx)?;
+    // Broadcast commit first. If a previous attempt already got it into the
+    // mempool or a block, skip it and carry on with the spell.
+    let commit_txid = match btc.send_raw_transaction(&commit_tx) {
+        Ok(txid) => txid,
+        Err(e) if is_already_broadcast_error(&e) => {
+            let txid = commit_tx.compute_txid();
+            log::warn!("Commit tx {} already broadcast, skipping: {}", txid, e);
+            txid
+        }
+        Err(e) => return Err(e.into()),
+    };
     log::debug!("Commit tx: {}", commit_txid);
 
     // Broadcast spell
     let spell_txid = btc.send_raw_transaction(&spell_tx)?;
     log::debug!("Spell tx: {}", spell_txid);
+    forget_spent_spells(&spell_tx);
 
     Ok(BroadcastNftResponse {
         commit_txid: commit_txid.to_string(),
@@ -1145,28 +3065,407 @@ pub fn broadcast_nft(
     })
 }
 
+// ============================================================================
+// Diagnostics
+// ============================================================================
+
+/// Outcome of one `doctor` check
+pub struct DoctorCheck {
+    pub name: &'static str,
+    /// A failed critical check means nothing will work until it's fixed
+    pub critical: bool,
+    pub result: Result<String, String>,
+}
+
+fn check(
+    name: &'static str,
+    critical: bool,
+    run: impl FnOnce() -> anyhow::Result<String>,
+) -> DoctorCheck {
+    DoctorCheck {
+        name,
+        critical,
+        result: run().map_err(|e| format!("{:#}", e)),
+    }
+}
+
+/// Check everything a create/update needs: node, wallet, contract, prover
+/// and funds. Never connects in a way that creates or loads a wallet.
+pub fn run_diagnostics() -> Vec<DoctorCheck> {
+    let endpoint = node_endpoint();
+    let node = endpoint
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("{:#}", e))
+        .and_then(|(url, auth)| Ok(Client::new(url, auth.clone())?));
+    let name = wallet_name();
+
+    let mut checks = vec![check("Node RPC", true, || {
+        let (url, _) = endpoint.as_ref().map_err(|e| anyhow::anyhow!("{:#}", e))?;
+        let node = node
+            .as_ref()
+            .map_err(|e| anyhow::anyhow!("{}: {:#}", url, e))?;
+        let info = node.get_blockchain_info()?;
+        Ok(format!("{} at {}, height {}", info.chain, url, info.blocks))
+    })];
+
+    let node_ok = checks[0].result.is_ok();
+    checks.push(check("Wallet", false, || {
+        let node = node
+            .as_ref()
+            .ok()
+            .filter(|_| node_ok)
+            .ok_or_else(|| anyhow::anyhow!("Node unreachable"))?;
+        if node.list_wallets()?.contains(&name) {
+            Ok(format!("'{}' loaded", name))
+        } else if node.list_wallet_dir()?.contains(&name) {
+            Ok(format!("'{}' exists, loaded on first use", name))
+        } else {
+            anyhow::bail!("'{}' not found (created on first use)", name)
+        }
+    }));
+
+    checks.push(check("Contract WASM", true, || {
+        let path = get_contract_path();
+        let size = fs::metadata(&path)
+            .map_err(|e| anyhow::anyhow!("{}: {}. Run: make contract", path.display(), e))?
+            .len();
+        Ok(format!("{} ({} bytes)", path.display(), size))
+    }));
+
+    checks.push(check("Contract VK", true, || {
+        let path = get_contract_vk_path();
+        let vk = local_contract_vk()
+            .ok_or_else(|| anyhow::anyhow!("{} missing. Run: make contract", path.display()))?;
+        if vk.len() != 64 || !vk.chars().all(|c| c.is_ascii_hexdigit()) {
+            anyhow::bail!("{} is not a 32-byte hex VK", path.display());
+        }
+        Ok(vk)
+    }));
+
+    checks.push(check("charms binary", true, || {
+        Ok(find_charms_binary()?.display().to_string())
+    }));
+
+    checks.push(check("Funding", false, || {
+        if !node_ok {
+            anyhow::bail!("Node unreachable");
+        }
+        let (url, auth) = endpoint.as_ref().map_err(|e| anyhow::anyhow!("{:#}", e))?;
+        let wallet = Client::new(
+            &format!("{}/wallet/{}", url.trim_end_matches('/'), name),
+            auth.clone(),
+        )?;
+        let mut spendable = Vec::new();
+        for utxo in wallet.list_unspent(None, None, None, None, None)? {
+            if is_spendable_funding(&wallet, &utxo)? {
+                spendable.push(utxo);
+            }
+        }
+        match spendable.iter().map(|u| u.amount.to_sat()).max() {
+            Some(largest) => Ok(format!(
+                "{} spendable UTXOs, largest {} sats",
+                spendable.len(),
+                largest
+            )),
+            None => anyhow::bail!("No spendable non-NFT UTXOs in '{}'", name),
+        }
+    }));
+
+    checks
+}
+
+/// Print the diagnostics table; fails if any critical check failed
+pub fn doctor() -> anyhow::Result<()> {
+    let checks = run_diagnostics();
+
+    println!("\n🩺 Environment check");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    for c in &checks {
+        let (mark, detail) = match &c.result {
+            Ok(detail) => ("✅", detail.as_str()),
+            Err(e) if c.critical => ("❌", e.as_str()),
+            Err(e) => ("⚠️ ", e.as_str()),
+        };
+        println!("{} {:<14} {}", mark, c.name, detail);
+    }
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+
+    let failed: Vec<_> = checks
+        .iter()
+        .filter(|c| c.critical && c.result.is_err())
+        .map(|c| c.name)
+        .collect();
+    if !failed.is_empty() {
+        anyhow::bail!("Critical checks failed: {}", failed.join(", "));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Offline Signing
+// ============================================================================
+
+/// Build an unsigned create funded from the node wallet and write it to
+/// `out` as JSON, to be signed on another machine
+pub fn export_create_unsigned(
+    btc: &Client,
+    prover: &dyn Prover,
+    habit_name: String,
+    out: &Path,
+) -> anyhow::Result<()> {
+    let (funding_utxo, funding_value, user_address) = get_funding_utxo(btc, &[])?;
+    let unsigned = create_nft_unsigned(
+        prover,
+        habit_name,
+        user_address,
+        funding_utxo,
+        funding_value,
+        CreateOptions::default(),
+    )?;
+
+    fs::write(out, serde_json::to_string_pretty(&unsigned)?)
+        .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", out.display(), e))?;
+    println!("✅ Unsigned create written to {}", out.display());
+    println!("   Predicted NFT UTXO: {}", unsigned.predicted_nft_utxo);
+    Ok(())
+}
+
+/// Build an unsigned update of `nft_utxo` funded from the node wallet and
+/// write it to `out` as JSON, to be signed on another machine
+pub fn export_update_unsigned(
+    btc: &Client,
+    prover: &dyn Prover,
+    nft_utxo: String,
+    out: &Path,
+) -> anyhow::Result<()> {
+    let owner = ensure_habit_nft(btc, &nft_utxo)?.owner;
+    let (funding_utxo, funding_value, _) = get_funding_utxo(btc, &[&nft_utxo])?;
+    let unsigned = update_nft_unsigned(
+        btc,
+        prover,
+        nft_utxo,
+        owner,
+        funding_utxo,
+        funding_value,
+        UpdateOptions::default(),
+    )?;
+
+    fs::write(out, serde_json::to_string_pretty(&unsigned)?)
+        .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", out.display(), e))?;
+    println!(
+        "✅ Unsigned update ({} → {} sessions) written to {}",
+        unsigned.current_sessions,
+        unsigned.new_sessions,
+        out.display()
+    );
+    Ok(())
+}
+
+/// Broadcast a commit/spell pair signed elsewhere, each file holding the
+/// signed transaction hex
+pub fn broadcast_from_files(btc: &Client, commit: &Path, spell: &Path) -> anyhow::Result<()> {
+    let read_hex = |path: &Path| -> anyhow::Result<String> {
+        Ok(fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?
+            .trim()
+            .to_string())
+    };
+
+    let result = broadcast_nft(btc, read_hex(commit)?, read_hex(spell)?)?;
+    println!("✅ Broadcast complete");
+    println!("   Commit tx: {}", result.commit_txid);
+    println!("   Spell tx: {}", result.spell_txid);
+    println!("   NFT UTXO: {}:0", result.spell_txid);
+    Ok(())
+}
+
+/// Decode a signed transaction and its spell and flag anything that looks
+/// off before broadcasting: missing signatures, a habit NFT output that isn't
+/// worth NFT_AMOUNT_SATS, dust outputs, or outputs exceeding inputs.
+pub fn inspect_signed_tx(btc: &Client, signed_hex: &str) -> anyhow::Result<TxInspection> {
+    let signed_hex = signed_hex.trim();
+    let tx: bitcoin::Transaction = bitcoin::consensus::deserialize(&hex::decode(signed_hex)?)?;
+    let mut anomalies = Vec::new();
+
+    for (i, input) in tx.input.iter().enumerate() {
+        if input.witness.is_empty() && input.script_sig.is_empty() {
+            anomalies.push(format!("Input {} is not signed", i));
+        }
+    }
+
+    let total_out_sats: u64 = tx.output.iter().map(|out| out.value.to_sat()).sum();
+    for (i, out) in tx.output.iter().enumerate() {
+        if !out.script_pubkey.is_op_return() && out.value < out.script_pubkey.minimal_non_dust() {
+            anomalies.push(format!(
+                "Output {} is dust ({} sats)",
+                i,
+                out.value.to_sat()
+            ));
+        }
+    }
+
+    // Fee is only known if every prevout is still unspent on the node
+    let mut in_sats = Some(0u64);
+    for input in &tx.input {
+        let prevout = &input.previous_output;
+        let value = btc
+            .get_tx_out(&prevout.txid, prevout.vout, Some(true))?
+            .map(|out| out.value.to_sat());
+        in_sats = in_sats.zip(value).map(|(sum, v)| sum + v);
+    }
+    let fee = match in_sats {
+        Some(in_sats) if in_sats < total_out_sats => {
+            anomalies.push(format!(
+                "Outputs ({} sats) exceed inputs ({} sats)",
+                total_out_sats, in_sats
+            ));
+            None
+        }
+        Some(in_sats) => Some(in_sats - total_out_sats),
+        None => None,
+    };
+
+    // A commit tx has no spell; only check the NFT output when there is one
+    let nft_output_present = match decode_spell_hex(signed_hex) {
+        Ok(spell) => {
+            let has_charm = spell["outs"][0]["charms"]["$0000"].is_object();
+            if !has_charm {
+                anomalies.push("Spell has no habit charm on output 0".to_string());
+            }
+            match tx.output.first() {
+                Some(out) if has_charm && out.value.to_sat() != NFT_AMOUNT_SATS => {
+                    anomalies.push(format!(
+                        "NFT output holds {} sats, expected {}",
+                        out.value.to_sat(),
+                        NFT_AMOUNT_SATS
+                    ));
+                }
+                None if has_charm => anomalies.push("Spell tx has no outputs".to_string()),
+                _ => {}
+            }
+            has_charm
+        }
+        Err(e) => {
+            log::debug!("No spell decoded from tx: {}", e);
+            false
+        }
+    };
+
+    Ok(TxInspection {
+        txid: tx.compute_txid().to_string(),
+        num_inputs: tx.input.len(),
+        num_outputs: tx.output.len(),
+        nft_output_present,
+        total_out_sats,
+        fee,
+        anomalies,
+    })
+}
+
+/// Print an inspection of a signed transaction
+pub fn inspect_tx(btc: &Client, signed_hex: String) -> anyhow::Result<()> {
+    let inspection = inspect_signed_tx(btc, &signed_hex)?;
+    println!("{}", serde_json::to_string_pretty(&inspection)?);
+    if !inspection.anomalies.is_empty() {
+        anyhow::bail!("{} anomalies found", inspection.anomalies.len());
+    }
+    Ok(())
+}
+
+/// Look up how deeply a transaction is confirmed and in which block
+pub fn confirmation_detail(btc: &Client, txid: &str) -> anyhow::Result<ConfirmationDetail> {
+    let txid = bitcoin::Txid::from_str(txid)?;
+    let info = btc.get_raw_transaction_info(&txid, None)?;
+
+    let Some(block_hash) = info.blockhash else {
+        return Ok(ConfirmationDetail {
+            txid: txid.to_string(),
+            confirmations: 0,
+            block_hash: None,
+            block_height: None,
+        });
+    };
+
+    // A block that left the active chain reports -1 confirmations
+    let header = btc.get_block_header_info(&block_hash)?;
+    if header.confirmations < 0 {
+        log::warn!(
+            "Block {} holding {} is no longer in the active chain",
+            block_hash,
+            txid
+        );
+    }
+
+    Ok(ConfirmationDetail {
+        txid: txid.to_string(),
+        confirmations: header.confirmations.max(0) as u32,
+        block_hash: Some(block_hash.to_string()),
+        block_height: Some(header.height as u64),
+    })
+}
+
 // ============================================================================
 // Transaction Signing & Broadcasting
 // ============================================================================
 
+/// The wallet only partly signed a transaction (watch-only keys, multisig,
+/// missing prevout info). Lists every input it couldn't sign.
+#[derive(Debug)]
+pub struct SigningError {
+    pub tx: &'static str,
+    /// (outpoint, wallet error) per unsigned input
+    pub inputs: Vec<(String, String)>,
+}
+
+impl std::fmt::Display for SigningError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to sign {} transaction", self.tx)?;
+        if self.inputs.is_empty() {
+            return write!(f, " (wallet reported no per-input errors)");
+        }
+        let inputs: Vec<String> = self
+            .inputs
+            .iter()
+            .map(|(outpoint, error)| format!("{}: {}", outpoint, error))
+            .collect();
+        write!(f, ", unsigned inputs: {}", inputs.join("; "))
+    }
+}
+
+impl std::error::Error for SigningError {}
+
+/// Fail with a SigningError naming each unsigned input unless signing completed
+pub fn ensure_signed(
+    tx: &'static str,
+    result: &bitcoincore_rpc::json::SignRawTransactionResult,
+) -> Result<(), SigningError> {
+    if result.complete {
+        return Ok(());
+    }
+    let inputs = result
+        .errors
+        .iter()
+        .flatten()
+        .map(|e| (format!("{}:{}", e.txid, e.vout), e.error.clone()))
+        .collect();
+    Err(SigningError { tx, inputs })
+}
+
 pub fn sign_and_broadcast_create(
     btc: &Client,
-    bitcoin_txs: Vec<bitcoin::Transaction>,
+    commit_tx: &bitcoin::Transaction,
+    spell_tx: &bitcoin::Transaction,
+    progress: Option<&ProgressSender>,
 ) -> anyhow::Result<serde_json::Value> {
-    println!(
-        "DEBUG: sign_and_broadcast_create: Starting with {} txs",
-        bitcoin_txs.len()
-    );
+    println!("DEBUG: sign_and_broadcast_create: Starting");
     log::debug!("Signing transactions");
+    report(progress, Progress::Signing);
 
     println!("DEBUG: Signing commit transaction...");
-    let signed_commit = btc.sign_raw_transaction_with_wallet(&bitcoin_txs[0], None, None)?;
-    if !signed_commit.complete {
-        anyhow::bail!("Failed to sign commit transaction");
-    }
+    let signed_commit = btc.sign_raw_transaction_with_wallet(commit_tx, None, None)?;
+    ensure_signed("commit", &signed_commit)?;
     println!("DEBUG: Commit tx signed");
 
-    let commit_tx = &bitcoin_txs[0];
     let commit_script_pubkey = commit_tx.output[0].script_pubkey.clone();
     let commit_amount_btc = commit_tx.output[0].value.to_btc();
 
@@ -1179,14 +3478,12 @@ pub fn sign_and_broadcast_create(
     };
 
     println!("DEBUG: Signing spell transaction...");
-    let signed_spell =
-        btc.sign_raw_transaction_with_wallet(&bitcoin_txs[1], Some(&[prevout]), None)?;
+    let signed_spell = btc.sign_raw_transaction_with_wallet(spell_tx, Some(&[prevout]), None)?;
 
-    if !signed_spell.complete {
-        anyhow::bail!("Failed to sign spell transaction");
-    }
+    ensure_signed("spell", &signed_spell)?;
     println!("DEBUG: Spell tx signed");
     log::debug!("Broadcasting transactions");
+    report(progress, Progress::Broadcasting);
 
     println!("DEBUG: Broadcasting commit tx...");
     let commit_txid = btc.send_raw_transaction(&signed_commit.hex)?;
@@ -1309,35 +3606,39 @@ pub fn sign_and_broadcast_create(
 
 fn sign_and_broadcast_update(
     btc: &Client,
-    bitcoin_txs: Vec<bitcoin::Transaction>,
+    commit_tx: &bitcoin::Transaction,
+    spell_tx: &bitcoin::Transaction,
     nft_txid: &str,
     nft_utxo: &str,
+    progress: Option<&ProgressSender>,
 ) -> anyhow::Result<serde_json::Value> {
-    println!(
-        "DEBUG: sign_and_broadcast_update: Starting with {} txs",
-        bitcoin_txs.len()
-    );
+    println!("DEBUG: sign_and_broadcast_update: Starting");
     log::debug!("Signing update transactions");
+    report(progress, Progress::Signing);
 
     println!("DEBUG: Signing commit transaction...");
-    let signed_commit = btc.sign_raw_transaction_with_wallet(&bitcoin_txs[0], None, None)?;
-    if !signed_commit.complete {
-        anyhow::bail!("Failed to sign commit transaction");
-    }
+    let signed_commit = btc.sign_raw_transaction_with_wallet(commit_tx, None, None)?;
+    ensure_signed("commit", &signed_commit)?;
     println!("DEBUG: Commit tx signed");
 
-    let nft_tx_raw = btc.get_raw_transaction(&bitcoin::Txid::from_str(nft_txid)?, None)?;
+    let nft_tx_raw: bitcoin::Transaction = bitcoin::consensus::deserialize(&hex::decode(
+        fetch_raw_tx_hex(btc, &bitcoin::Txid::from_str(nft_txid)?)?,
+    )?)?;
     let nft_vout: u32 = nft_utxo.split(':').nth(1).unwrap().parse()?;
+    let nft_output = nft_tx_raw
+        .output
+        .get(nft_vout as usize)
+        .ok_or_else(|| anyhow::anyhow!("NFT tx {} has no output {}", nft_txid, nft_vout))?;
 
+    // Sign against the value actually on chain, not the default mint value
     let nft_prevout = bitcoincore_rpc::json::SignRawTransactionInput {
         txid: bitcoin::Txid::from_str(nft_txid)?,
         vout: nft_vout,
-        script_pub_key: nft_tx_raw.output[nft_vout as usize].script_pubkey.clone(),
+        script_pub_key: nft_output.script_pubkey.clone(),
         redeem_script: None,
-        amount: Some(bitcoin::Amount::from_sat(NFT_AMOUNT_SATS)),
+        amount: Some(nft_output.value),
     };
 
-    let commit_tx = &bitcoin_txs[0];
     let commit_prevout = bitcoincore_rpc::json::SignRawTransactionInput {
         txid: commit_tx.compute_txid(),
         vout: 0,
@@ -1347,20 +3648,15 @@ fn sign_and_broadcast_update(
     };
 
     println!("DEBUG: Signing spell transaction...");
-    let signed_spell = btc.sign_raw_transaction_with_wallet(
-        &bitcoin_txs[1],
-        Some(&[nft_prevout, commit_prevout]),
-        None,
-    )?;
+    let signed_spell =
+        btc.sign_raw_transaction_with_wallet(spell_tx, Some(&[nft_prevout, commit_prevout]), None)?;
 
-    if !signed_spell.complete {
-        let errors = signed_spell.errors.unwrap_or_default();
-        anyhow::bail!("Failed to sign spell transaction: {:?}", errors);
-    }
+    ensure_signed("spell", &signed_spell)?;
     println!("DEBUG: Spell tx signed");
 
     // Always use sequential broadcasting for updates (more reliable)
     println!("DEBUG: Broadcasting transactions sequentially...");
+    report(progress, Progress::Broadcasting);
 
     println!("DEBUG: Broadcasting commit tx...");
     let commit_txid = btc.send_raw_transaction(&signed_commit.hex)?;
@@ -1369,6 +3665,7 @@ fn sign_and_broadcast_update(
     println!("DEBUG: Broadcasting spell tx...");
     let spell_txid = btc.send_raw_transaction(&signed_spell.hex)?;
     println!("DEBUG: Spell tx broadcast: {}", spell_txid);
+    forget_spent_spells(spell_tx);
 
     log::info!("NFT updated - Spell TXID: {}", spell_txid);
 
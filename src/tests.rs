@@ -1,5 +1,4 @@
 use crate::*;
-use serde_json::{json, Value};
 use serial_test::serial;
 use std::str::FromStr;
 use std::{env, time::SystemTime};
@@ -21,12 +20,10 @@ fn get_bitcoincore_rpc_client(node: &Node) -> anyhow::Result<BitcoinCoreClient>
     let cookie_values = params
         .get_cookie_values()?
         .ok_or_else(|| anyhow::anyhow!("No cookie values"))?;
+    let auth = Auth::UserPass(cookie_values.user, cookie_values.password);
 
     let base_url = format!("http://{}", params.rpc_socket);
-    let base_client = BitcoinCoreClient::new(
-        &base_url,
-        Auth::UserPass(cookie_values.user.clone(), cookie_values.password.clone()),
-    )?;
+    let base_client = BitcoinCoreClient::new(&base_url, auth.clone())?;
 
     let wallet_name = format!(
         "test_{}",
@@ -36,65 +33,15 @@ fn get_bitcoincore_rpc_client(node: &Node) -> anyhow::Result<BitcoinCoreClient>
             .as_nanos()
     );
 
-    // Try simple create wallet first
-    match base_client.create_wallet(&wallet_name, None, None, None, None) {
-        Ok(_) => {
-            println!("✓ Created wallet");
-
-            // Now set it to descriptor mode using upgradewallet
-            let wallet_url = format!("http://{}/wallet/{}", params.rpc_socket, wallet_name);
-            let wallet_client = BitcoinCoreClient::new(
-                &wallet_url,
-                Auth::UserPass(cookie_values.user.clone(), cookie_values.password.clone()),
-            )?;
-
-            // Try to upgrade to descriptor wallet
-            let _ = wallet_client.call::<serde_json::Value>("upgradewallet", &[json!(169900)]);
-
-            Ok(wallet_client)
-        }
-        Err(e) => {
-            println!("⚠ Create failed ({}), trying to load existing wallet...", e);
-
-            // Try to load existing wallet
-            let _ = base_client.load_wallet(&wallet_name);
-
-            let wallet_url = format!("http://{}/wallet/{}", params.rpc_socket, wallet_name);
-            let wallet_client = BitcoinCoreClient::new(
-                &wallet_url,
-                Auth::UserPass(cookie_values.user, cookie_values.password),
-            )?;
-
-            Ok(wallet_client)
-        }
-    }
+    ensure_wallet(&base_client, &base_url, auth, &wallet_name)
 }
 
-fn _print_spell(client: &bitcoincore_rpc::Client, txid: &Txid) -> anyhow::Result<()> {
-    // Get raw transaction hex from RPC
-    let tx_hex = client.get_raw_transaction_hex(txid, None)?;
-
-    // Run charms CLI to decode the spell
-    let output = std::process::Command::new("charms")
-        .args(&["tx", "show-spell", "--tx", &tx_hex, "--mock", "--json"])
-        .output()?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        anyhow::bail!("Failed to decode spell: {}", stderr);
-    }
-
-    // Parse JSON output
-    let spell: Value = serde_json::from_slice(&output.stdout)?;
-
-    // Pretty-print the spell
-    println!(
-        "Spell for tx {}:\n{}",
-        txid,
-        serde_json::to_string_pretty(&spell)?
-    );
-
-    Ok(())
+fn extract_nft_metadata(
+    client: &BitcoinCoreClient,
+    txid: &str,
+) -> anyhow::Result<(String, u64, String)> {
+    let metadata = fetch_nft_metadata(client, txid)?;
+    Ok((metadata.habit_name, metadata.total_sessions, metadata.owner))
 }
 
 // ============================================================================
@@ -106,6 +53,17 @@ struct TestBitcoin {
     client: BitcoinCoreClient,
 }
 
+/// Blocks before a coinbase output can be spent
+const COINBASE_MATURITY: u64 = 100;
+
+/// The contract's MIN_UPDATE_INTERVAL_SECS
+const CONTRACT_UPDATE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How long to wait for the wallet to see a mined transaction
+const CONFIRMATION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Node with a single mature coinbase, enough for tests that chain their
+/// funding through change outputs
 fn setup_test_bitcoin() -> anyhow::Result<TestBitcoin> {
     if env::var("TEMPDIR_ROOT").is_err() {
         env::set_var("TEMPDIR_ROOT", "/tmp/");
@@ -129,7 +87,11 @@ fn setup_test_bitcoin() -> anyhow::Result<TestBitcoin> {
         .get_new_address(None, None)?
         .require_network(bitcoin::Network::Regtest)?;
 
-    client.generate_to_address(101, &mining_addr)?;
+    let hashes = client.generate_to_address(COINBASE_MATURITY + 1, &mining_addr)?;
+    if let Some(last) = hashes.last() {
+        let coinbase = client.get_block(last)?.txdata[0].compute_txid();
+        wait_for_confirmation(&client, &coinbase, CONFIRMATION_TIMEOUT)?;
+    }
 
     Ok(TestBitcoin {
         _node: node,
@@ -137,15 +99,47 @@ fn setup_test_bitcoin() -> anyhow::Result<TestBitcoin> {
     })
 }
 
+/// Poll until the wallet reports `txid` as confirmed, instead of sleeping
+/// and hoping the wallet has caught up with the chain
+fn wait_for_confirmation(
+    client: &BitcoinCoreClient,
+    txid: &Txid,
+    timeout: std::time::Duration,
+) -> anyhow::Result<()> {
+    let started = std::time::Instant::now();
+    loop {
+        if let Ok(tx) = client.get_transaction(txid, None) {
+            if tx.info.confirmations > 0 {
+                return Ok(());
+            }
+        }
+        if started.elapsed() > timeout {
+            anyhow::bail!("{} not confirmed after {:?}", txid, timeout);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+}
+
+/// Sleep out whatever is left of the contract's update interval since the
+/// previous update was built (proving often already used most of it)
+fn wait_for_update_window(updated_at: std::time::Instant) {
+    if let Some(remaining) = CONTRACT_UPDATE_INTERVAL.checked_sub(updated_at.elapsed()) {
+        std::thread::sleep(remaining);
+    }
+}
+
 impl TestBitcoin {
+    /// Mine one block and wait until the wallet has processed it (its
+    /// coinbase shows as confirmed), so UTXO lookups right after see it
     fn mine_block(&self) -> anyhow::Result<()> {
         let addr = self
             .client
             .get_new_address(None, None)?
             .require_network(bitcoin::Network::Regtest)?;
 
-        self.client.generate_to_address(1, &addr)?;
-        Ok(())
+        let hash = self.client.generate_to_address(1, &addr)?[0];
+        let coinbase = self.client.get_block(&hash)?.txdata[0].compute_txid();
+        wait_for_confirmation(&self.client, &coinbase, CONFIRMATION_TIMEOUT)
     }
 
     fn get_new_address(&self) -> anyhow::Result<bitcoin::Address> {
@@ -207,7 +201,7 @@ fn sign_transactions(
     let spell_tx: bitcoin::Transaction = bitcoin::consensus::deserialize(&hex::decode(spell_hex)?)?;
 
     let signed_commit = client.sign_raw_transaction_with_wallet(&commit_tx, None, None)?;
-    assert!(signed_commit.complete, "commit tx signing incomplete");
+    ensure_signed("commit", &signed_commit)?;
 
     let mut prevouts = vec![bitcoincore_rpc::json::SignRawTransactionInput {
         txid: commit_tx.compute_txid(),
@@ -225,12 +219,12 @@ fn sign_transactions(
             vout: nft.vout,
             script_pub_key: nft_tx.output[nft.vout as usize].script_pubkey.clone(),
             redeem_script: None,
-            amount: Some(bitcoin::Amount::from_sat(1000)),
+            amount: Some(nft_tx.output[nft.vout as usize].value),
         });
     }
 
     let signed_spell = client.sign_raw_transaction_with_wallet(&spell_tx, Some(&prevouts), None)?;
-    assert!(signed_spell.complete, "spell tx signing incomplete");
+    ensure_signed("spell", &signed_spell)?;
 
     Ok(SignedTransactions {
         commit_hex: hex::encode(&signed_commit.hex),
@@ -242,7 +236,7 @@ fn verify_spell_has_charms(client: &BitcoinCoreClient, txid: &Txid) -> anyhow::R
     let tx_hex = client.get_raw_transaction_hex(txid, None)?;
 
     let output = std::process::Command::new("charms")
-        .args(&["tx", "show-spell", "--tx", &tx_hex, "--mock", "--json"])
+        .args(["tx", "show-spell", "--tx", &tx_hex, "--mock", "--json"])
         .output()?;
 
     assert!(output.status.success(), "charms decode failed");
@@ -259,6 +253,93 @@ fn verify_spell_has_charms(client: &BitcoinCoreClient, txid: &Txid) -> anyhow::R
     Ok(())
 }
 
+/// In-process prover that skips `charms` and returns a plain commit/spell
+/// pair shaped like the real one. Spells it was asked to prove are recorded.
+#[derive(Default)]
+struct MockProver {
+    spells: std::sync::Mutex<Vec<serde_json::Value>>,
+}
+
+impl MockProver {
+    const FEE_SATS: u64 = 500;
+    /// Stands in for the contract VK so mock tests don't need the WASM
+    const VK: &'static str = "0000000000000000000000000000000000000000000000000000000000000000";
+}
+
+impl Prover for MockProver {
+    fn prove(
+        &self,
+        spell: &serde_json::Value,
+        _prev_txs: &[String],
+        funding_utxo: &str,
+        funding_utxo_value: u64,
+        change_address: &str,
+        _fee_rate: f64,
+    ) -> anyhow::Result<Vec<charms_client::tx::Tx>> {
+        use bitcoin::{absolute::LockTime, transaction::Version, Amount, OutPoint, TxIn, TxOut};
+
+        self.spells.lock().unwrap().push(spell.clone());
+
+        let script_for = |address: &str| -> anyhow::Result<bitcoin::ScriptBuf> {
+            Ok(bitcoin::Address::from_str(address)?
+                .assume_checked()
+                .script_pubkey())
+        };
+        let nft_address = spell["outs"][0]["address"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("spell has no output address"))?;
+        let nft_sats = spell["outs"][0]["sats"].as_u64().unwrap_or(1000);
+
+        let (txid, vout) = parse_utxo(funding_utxo)?;
+        let commit_value = funding_utxo_value - Self::FEE_SATS;
+        let commit_tx = bitcoin::Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint { txid, vout },
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(commit_value),
+                script_pubkey: script_for(change_address)?,
+            }],
+        };
+
+        let spell_tx = bitcoin::Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: commit_tx.compute_txid(),
+                    vout: 0,
+                },
+                ..Default::default()
+            }],
+            output: vec![
+                TxOut {
+                    value: Amount::from_sat(nft_sats),
+                    script_pubkey: script_for(nft_address)?,
+                },
+                TxOut {
+                    value: Amount::from_sat(commit_value - nft_sats - Self::FEE_SATS),
+                    script_pubkey: script_for(change_address)?,
+                },
+            ],
+        };
+
+        Ok(vec![commit_tx, spell_tx]
+            .into_iter()
+            .map(|tx| {
+                charms_client::tx::Tx::Bitcoin(charms_client::bitcoin_tx::BitcoinTx::Simple(tx))
+            })
+            .collect())
+    }
+
+    fn vk(&self) -> anyhow::Result<String> {
+        Ok(Self::VK.to_string())
+    }
+}
+
 // ============================================================================
 // Tests
 // ============================================================================
@@ -279,10 +360,12 @@ fn create_nft_works() {
     // Create unsigned transactions
     let habit_name = unique_habit_name("Morning Meditation");
     let unsigned = create_nft_unsigned(
+        &CliProver::default(),
         habit_name,
         user_addr.to_string(),
         format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
         funding_utxo.amount.to_sat(),
+        CreateOptions::default(),
     )
     .expect("create unsigned");
 
@@ -328,7 +411,13 @@ fn update_nft_works() {
 
     // Create initial NFT
     let habit_name = unique_habit_name("Update Test");
-    let nft_txid = create_nft(&bitcoin.client, habit_name).expect("create NFT");
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        habit_name,
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
     bitcoin.mine_block().expect("mine block");
 
     // Get NFT and funding UTXOs
@@ -342,10 +431,12 @@ fn update_nft_works() {
     // Create unsigned update transactions
     let unsigned = update_nft_unsigned(
         &bitcoin.client,
+        &CliProver::default(),
         format!("{}:{}", nft_utxo.txid, nft_utxo.vout),
         owner_addr.to_string(),
         format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
         funding_utxo.amount.to_sat(),
+        UpdateOptions::default(),
     )
     .expect("create unsigned update");
 
@@ -392,7 +483,13 @@ fn cli_create_nft_works() {
     let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
 
     let habit_name = unique_habit_name("CLI Test Habit");
-    let nft_txid = create_nft(&bitcoin.client, habit_name.clone()).expect("create NFT");
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        habit_name.clone(),
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
 
     bitcoin.mine_block().expect("mine block");
 
@@ -407,9 +504,9 @@ fn cli_create_nft_works() {
     assert_eq!(sessions, 0);
 }
 
-#[tokio::test]
+#[test]
 #[serial]
-async fn cli_update_nft_works() {
+fn cli_update_nft_works() {
     let contract_path = get_contract_path();
     assert!(
         contract_path.exists(),
@@ -420,7 +517,13 @@ async fn cli_update_nft_works() {
 
     // Create initial NFT
     let habit_name = unique_habit_name("CLI Update Test");
-    let nft_txid = create_nft(&bitcoin.client, habit_name.clone()).expect("create NFT");
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        habit_name.clone(),
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
     bitcoin.mine_block().expect("mine block");
 
     let nft_utxo = bitcoin.find_nft_by_txid(&nft_txid).expect("find NFT");
@@ -432,9 +535,13 @@ async fn cli_update_nft_works() {
     assert_eq!(initial_sessions, 0);
 
     // Update via CLI
-    update_nft(&bitcoin.client, nft_utxo_id.clone())
-        .await
-        .expect("update NFT");
+    update_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        nft_utxo_id.clone(),
+        UpdateOptions::default(),
+    )
+    .expect("update NFT");
     bitcoin.mine_block().expect("mine block");
 
     // Verify updated NFT
@@ -460,7 +567,13 @@ fn cli_view_nft_works() {
     let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
 
     let habit_name = unique_habit_name("CLI View Test");
-    let nft_txid = create_nft(&bitcoin.client, habit_name.clone()).expect("create NFT");
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        habit_name.clone(),
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
     bitcoin.mine_block().expect("mine block");
 
     let nft_utxo = bitcoin.find_nft_by_txid(&nft_txid).expect("find NFT");
@@ -490,7 +603,13 @@ fn app_preserves_owner_on_update() {
 
     // Create NFT
     let habit_name = unique_habit_name("Owner Preservation Test");
-    let nft_txid = create_nft(&bitcoin.client, habit_name).expect("create NFT");
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        habit_name,
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
     bitcoin.mine_block().expect("mine block");
 
     let (_, _, original_owner) =
@@ -502,10 +621,12 @@ fn app_preserves_owner_on_update() {
 
     let unsigned = update_nft_unsigned(
         &bitcoin.client,
+        &CliProver::default(),
         format!("{}:0", nft_txid),
         original_owner.clone(), // Use same owner
         format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
         funding_utxo.amount.to_sat(),
+        UpdateOptions::default(),
     )
     .expect("create unsigned update");
 
@@ -545,7 +666,13 @@ fn app_increments_sessions_correctly() {
 
     // Create NFT
     let habit_name = unique_habit_name("Session Increment Test");
-    let nft_txid = create_nft(&bitcoin.client, habit_name).expect("create NFT");
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        habit_name,
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
     bitcoin.mine_block().expect("mine block");
 
     // Verify starts at 0
@@ -559,10 +686,12 @@ fn app_increments_sessions_correctly() {
 
     let unsigned = update_nft_unsigned(
         &bitcoin.client,
+        &CliProver::default(),
         format!("{}:0", nft_txid),
         owner.clone(),
         format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
         funding_utxo.amount.to_sat(),
+        UpdateOptions::default(),
     )
     .expect("create unsigned update");
 
@@ -601,7 +730,13 @@ fn app_assigns_correct_badges() {
 
     // Create NFT (0 sessions = no badges)
     let habit_name = unique_habit_name("Badge Test");
-    let nft_txid = create_nft(&bitcoin.client, habit_name).expect("create NFT");
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        habit_name,
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
     bitcoin.mine_block().expect("mine block");
 
     let tx_hex_0 = bitcoin
@@ -610,7 +745,7 @@ fn app_assigns_correct_badges() {
         .unwrap();
 
     let spell_output_0 = std::process::Command::new("charms")
-        .args(&["tx", "show-spell", "--tx", &tx_hex_0, "--mock", "--json"])
+        .args(["tx", "show-spell", "--tx", &tx_hex_0, "--mock", "--json"])
         .output()
         .unwrap();
 
@@ -641,10 +776,12 @@ fn app_assigns_correct_badges() {
 
     let unsigned = update_nft_unsigned(
         &bitcoin.client,
+        &CliProver::default(),
         format!("{}:0", nft_txid),
         owner,
         format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
         funding_utxo.amount.to_sat(),
+        UpdateOptions::default(),
     )
     .expect("create unsigned update");
 
@@ -671,7 +808,7 @@ fn app_assigns_correct_badges() {
         .unwrap();
 
     let spell_output_1 = std::process::Command::new("charms")
-        .args(&["tx", "show-spell", "--tx", &tx_hex_1, "--mock", "--json"])
+        .args(["tx", "show-spell", "--tx", &tx_hex_1, "--mock", "--json"])
         .output()
         .unwrap();
 
@@ -704,7 +841,13 @@ fn app_extracts_metadata_correctly() {
 
     let habit_name = unique_habit_name("Metadata Test");
 
-    let nft_txid = create_nft(&bitcoin.client, habit_name.clone()).expect("create NFT");
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        habit_name.clone(),
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
     bitcoin.mine_block().expect("mine block");
 
     let (extracted_habit, sessions, owner) =
@@ -727,7 +870,13 @@ fn app_handles_multiple_updates() {
     let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
 
     let habit_name = unique_habit_name("Multiple Updates Test");
-    let mut current_txid = create_nft(&bitcoin.client, habit_name).expect("create NFT");
+    let mut current_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        habit_name,
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
     bitcoin.mine_block().expect("mine block");
 
     // First update doesn't need to wait (no last_updated in input)
@@ -739,12 +888,15 @@ fn app_handles_multiple_updates() {
 
     let unsigned = update_nft_unsigned(
         &bitcoin.client,
+        &CliProver::default(),
         format!("{}:0", current_txid),
         owner.clone(),
         format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
         funding_utxo.amount.to_sat(),
+        UpdateOptions::default(),
     )
     .expect("create unsigned update");
+    let mut updated_at = std::time::Instant::now();
 
     let signed = sign_transactions(
         &bitcoin.client,
@@ -760,25 +912,24 @@ fn app_handles_multiple_updates() {
     bitcoin.mine_block().expect("mine block");
     current_txid = broadcast.spell_txid;
 
-    // Subsequent updates need to wait 5 seconds
+    // Subsequent updates must respect the contract's update interval
     for expected_session in 2..=3 {
-        println!(
-            "Waiting 5 seconds before update to session {}...",
-            expected_session
-        );
-        std::thread::sleep(std::time::Duration::from_secs(5));
+        wait_for_update_window(updated_at);
 
         let nft_utxo = bitcoin.find_nft_by_txid(&current_txid).expect("find NFT");
         let funding_utxo = bitcoin.get_funding_utxo().expect("get funding");
 
         let unsigned = update_nft_unsigned(
             &bitcoin.client,
+            &CliProver::default(),
             format!("{}:0", current_txid),
             owner.clone(),
             format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
             funding_utxo.amount.to_sat(),
+            UpdateOptions::default(),
         )
         .expect("create unsigned update");
+        updated_at = std::time::Instant::now();
 
         let signed = sign_transactions(
             &bitcoin.client,
@@ -814,7 +965,13 @@ fn contract_enforces_time_restriction() {
 
     // Create NFT and do first update
     let habit_name = unique_habit_name("Time Restriction Test");
-    let nft_txid = create_nft(&bitcoin.client, habit_name).expect("create NFT");
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        habit_name,
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
     bitcoin.mine_block().expect("mine block");
 
     let (_, _, owner) = extract_nft_metadata(&bitcoin.client, &nft_txid).expect("extract metadata");
@@ -824,10 +981,12 @@ fn contract_enforces_time_restriction() {
     // First update (should work - no previous timestamp)
     let unsigned = update_nft_unsigned(
         &bitcoin.client,
+        &CliProver::default(),
         format!("{}:0", nft_txid),
         owner.clone(),
         format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
         funding_utxo.amount.to_sat(),
+        UpdateOptions::default(),
     )
     .expect("create unsigned update");
 
@@ -850,10 +1009,12 @@ fn contract_enforces_time_restriction() {
 
     let result = update_nft_unsigned(
         &bitcoin.client,
+        &CliProver::default(),
         format!("{}:0", broadcast.spell_txid),
         owner,
         format!("{}:{}", funding_utxo_2.txid, funding_utxo_2.vout),
         funding_utxo_2.amount.to_sat(),
+        UpdateOptions::default(),
     );
 
     assert!(result.is_err(), "Update should fail when done too soon");
@@ -878,7 +1039,13 @@ fn contract_allows_update_after_waiting() {
 
     // Create NFT and do first update
     let habit_name = unique_habit_name("Wait Time Test");
-    let nft_txid = create_nft(&bitcoin.client, habit_name).expect("create NFT");
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        habit_name,
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
     bitcoin.mine_block().expect("mine block");
 
     let (_, _, owner) = extract_nft_metadata(&bitcoin.client, &nft_txid).expect("extract metadata");
@@ -888,12 +1055,15 @@ fn contract_allows_update_after_waiting() {
     // First update
     let unsigned = update_nft_unsigned(
         &bitcoin.client,
+        &CliProver::default(),
         format!("{}:0", nft_txid),
         owner.clone(),
         format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
         funding_utxo.amount.to_sat(),
+        UpdateOptions::default(),
     )
     .expect("create unsigned update");
+    let updated_at = std::time::Instant::now();
 
     let signed = sign_transactions(
         &bitcoin.client,
@@ -906,9 +1076,7 @@ fn contract_allows_update_after_waiting() {
         .expect("first update should succeed");
     bitcoin.mine_block().expect("mine block");
 
-    // Wait 5 seconds
-    println!("Waiting 5 seconds for time restriction...");
-    std::thread::sleep(std::time::Duration::from_secs(5));
+    wait_for_update_window(updated_at);
 
     // Try to update after waiting (should SUCCEED)
     let nft_utxo_2 = bitcoin
@@ -918,10 +1086,12 @@ fn contract_allows_update_after_waiting() {
 
     let unsigned_2 = update_nft_unsigned(
         &bitcoin.client,
+        &CliProver::default(),
         format!("{}:0", broadcast.spell_txid),
         owner,
         format!("{}:{}", funding_utxo_2.txid, funding_utxo_2.vout),
         funding_utxo_2.amount.to_sat(),
+        UpdateOptions::default(),
     )
     .expect("update should succeed after waiting");
 
@@ -941,3 +1111,1988 @@ fn contract_allows_update_after_waiting() {
         extract_nft_metadata(&bitcoin.client, &broadcast_2.spell_txid).expect("extract metadata");
     assert_eq!(sessions, 2, "Should have 2 sessions after second update");
 }
+
+#[test]
+#[serial]
+fn broadcast_nft_skips_already_broadcast_commit() {
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+    let user_addr = bitcoin.get_new_address().expect("get address");
+    let funding_utxo = bitcoin.get_first_utxo().expect("get funding utxo");
+
+    let habit_name = unique_habit_name("Rebroadcast Test");
+    let unsigned = create_nft_unsigned(
+        &MockProver::default(),
+        habit_name,
+        user_addr.to_string(),
+        format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
+        funding_utxo.amount.to_sat(),
+        CreateOptions::default(),
+    )
+    .expect("create unsigned");
+
+    let signed = sign_transactions(
+        &bitcoin.client,
+        &unsigned.commit_tx_hex,
+        &unsigned.spell_tx_hex,
+        None,
+    )
+    .expect("sign transactions");
+
+    // Simulate a crash after the commit went out but before the spell did
+    let commit_tx: bitcoin::Transaction =
+        bitcoin::consensus::deserialize(&hex::decode(&signed.commit_hex).unwrap()).unwrap();
+    bitcoin
+        .client
+        .send_raw_transaction(&commit_tx)
+        .expect("broadcast commit");
+    bitcoin.mine_block().expect("mine block");
+
+    // Retrying the whole broadcast must skip the confirmed commit
+    let broadcast = broadcast_nft(&bitcoin.client, signed.commit_hex, signed.spell_hex)
+        .expect("retry broadcast should succeed");
+    assert_eq!(broadcast.commit_txid, unsigned.commit_txid);
+
+    bitcoin.mine_block().expect("mine block");
+
+    let nft_utxo = bitcoin
+        .find_nft_by_txid(&broadcast.spell_txid)
+        .expect("find NFT");
+    assert_eq!(nft_utxo.amount.to_sat(), 1000);
+}
+
+#[test]
+#[serial]
+fn update_rejects_non_nft_utxo() {
+    let contract_path = get_contract_path();
+    assert!(
+        contract_path.exists(),
+        "Contract WASM required. Run: make contract"
+    );
+
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let habit_name = unique_habit_name("Non NFT Test");
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        habit_name,
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let (_, _, owner) = extract_nft_metadata(&bitcoin.client, &nft_txid).expect("extract metadata");
+    let funding_utxo = bitcoin.get_funding_utxo().expect("get funding");
+    let funding_id = format!("{}:{}", funding_utxo.txid, funding_utxo.vout);
+
+    // Pass a plain funding UTXO where the NFT is expected
+    let result = update_nft_unsigned(
+        &bitcoin.client,
+        &CliProver::default(),
+        funding_id.clone(),
+        owner,
+        funding_id,
+        funding_utxo.amount.to_sat(),
+        UpdateOptions::default(),
+    );
+
+    assert!(result.is_err(), "Update must reject a non-NFT UTXO");
+    let err_msg = result.unwrap_err().to_string();
+    assert!(
+        err_msg.contains("is not a habit NFT"),
+        "Error should say the UTXO is not a habit NFT. Got: {}",
+        err_msg
+    );
+}
+
+#[test]
+#[serial]
+fn back_to_back_updates_produce_distinct_txs() {
+    let contract_path = get_contract_path();
+    assert!(
+        contract_path.exists(),
+        "Contract WASM required. Run: make contract"
+    );
+
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let habit_name = unique_habit_name("Nonce Test");
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        habit_name,
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let (_, _, owner) = extract_nft_metadata(&bitcoin.client, &nft_txid).expect("extract metadata");
+    let funding_utxo = bitcoin.get_funding_utxo().expect("get funding");
+
+    // Same NFT, same funding, same second: only the nonce differs
+    let build = || {
+        update_nft_unsigned(
+            &bitcoin.client,
+            &CliProver::default(),
+            format!("{}:0", nft_txid),
+            owner.clone(),
+            format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
+            funding_utxo.amount.to_sat(),
+            UpdateOptions::default(),
+        )
+        .expect("create unsigned update")
+    };
+    let first = build();
+    let second = build();
+
+    let spell_txid = |hex_str: &str| {
+        let tx: bitcoin::Transaction =
+            bitcoin::consensus::deserialize(&hex::decode(hex_str).unwrap()).unwrap();
+        tx.compute_txid()
+    };
+
+    assert_ne!(
+        spell_txid(&first.spell_tx_hex),
+        spell_txid(&second.spell_tx_hex),
+        "Consecutive updates must not collide"
+    );
+}
+
+#[test]
+#[serial]
+fn update_nft_with_extra_output() {
+    let contract_path = get_contract_path();
+    assert!(
+        contract_path.exists(),
+        "Contract WASM required. Run: make contract"
+    );
+
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let habit_name = unique_habit_name("Extra Output Test");
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        habit_name,
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let (_, _, owner) = extract_nft_metadata(&bitcoin.client, &nft_txid).expect("extract metadata");
+    let nft_utxo = bitcoin.find_nft_by_txid(&nft_txid).expect("find NFT");
+    let funding_utxo = bitcoin.get_funding_utxo().expect("get funding");
+    let payee = bitcoin.get_new_address().expect("get address");
+
+    let unsigned = update_nft_unsigned(
+        &bitcoin.client,
+        &CliProver::default(),
+        format!("{}:0", nft_txid),
+        owner,
+        format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
+        funding_utxo.amount.to_sat(),
+        UpdateOptions {
+            extra_outputs: vec![(payee.to_string(), 5000)],
+            ..Default::default()
+        },
+    )
+    .expect("create unsigned update");
+
+    let signed = sign_transactions(
+        &bitcoin.client,
+        &unsigned.commit_tx_hex,
+        &unsigned.spell_tx_hex,
+        Some(&nft_utxo),
+    )
+    .expect("sign transactions");
+
+    let broadcast =
+        broadcast_nft(&bitcoin.client, signed.commit_hex, signed.spell_hex).expect("broadcast");
+    bitcoin.mine_block().expect("mine block");
+
+    // NFT stays at vout 0, the payment follows it
+    let spell_tx = bitcoin
+        .client
+        .get_raw_transaction(&Txid::from_str(&broadcast.spell_txid).unwrap(), None)
+        .expect("get spell tx");
+    assert_eq!(spell_tx.output[0].value.to_sat(), 1000);
+    assert_eq!(spell_tx.output[1].value.to_sat(), 5000);
+    assert_eq!(spell_tx.output[1].script_pubkey, payee.script_pubkey());
+
+    let (_, sessions, _) =
+        extract_nft_metadata(&bitcoin.client, &broadcast.spell_txid).expect("extract metadata");
+    assert_eq!(sessions, 1);
+}
+
+#[test]
+#[serial]
+fn locate_finds_nft_by_app_id() {
+    let contract_path = get_contract_path();
+    assert!(
+        contract_path.exists(),
+        "Contract WASM required. Run: make contract"
+    );
+
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let habit_name = unique_habit_name("Locate Test");
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        habit_name,
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let metadata = fetch_nft_metadata(&bitcoin.client, &nft_txid).expect("fetch metadata");
+
+    let located = find_current_utxo_by_app_id(&bitcoin.client, &metadata.app_id)
+        .expect("locate NFT")
+        .expect("NFT should be found");
+    assert_eq!(located, format!("{}:0", nft_txid));
+
+    // Updates keep the app id, so the mint's id still finds the NFT afterwards
+    update_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        located,
+        UpdateOptions::default(),
+    )
+    .expect("update NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let updated = bitcoin.find_nft_utxo().expect("find updated NFT");
+    let relocated = find_current_utxo_by_app_id(&bitcoin.client, &metadata.app_id)
+        .expect("locate NFT")
+        .expect("updated NFT should be found");
+    assert_eq!(relocated, format!("{}:{}", updated.txid, updated.vout));
+
+    let missing = find_current_utxo_by_app_id(&bitcoin.client, "n/unknown/vk").expect("locate");
+    assert!(missing.is_none());
+}
+
+#[test]
+#[serial]
+fn batch_view_reports_per_utxo_results() {
+    let contract_path = get_contract_path();
+    assert!(
+        contract_path.exists(),
+        "Contract WASM required. Run: make contract"
+    );
+
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let habit_name = unique_habit_name("Batch View Test");
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        habit_name.clone(),
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let nft_utxo = format!("{}:0", nft_txid);
+    let results = fetch_nft_metadata_batch(
+        &bitcoin.client,
+        &[nft_utxo.clone(), nft_utxo.clone(), "not-a-utxo".to_string()],
+    );
+
+    assert_eq!(results.len(), 3);
+    for (utxo, result) in &results[..2] {
+        assert_eq!(utxo, &nft_utxo);
+        assert_eq!(result.as_ref().expect("metadata").habit_name, habit_name);
+    }
+    assert!(results[2].1.is_err(), "Malformed UTXO must fail on its own");
+}
+
+#[test]
+#[serial]
+fn missing_prover_binary_is_unavailable() {
+    env::set_var("CHARMS_BIN", "/nonexistent/charms");
+    let result = prove_with_cli(
+        &serde_json::json!({}),
+        "contracts/habit-tracker.wasm",
+        &[],
+        "0000000000000000000000000000000000000000000000000000000000000000:0",
+        10_000,
+        "bcrt1qunused",
+        2.0,
+        true,
+    );
+    env::remove_var("CHARMS_BIN");
+
+    let err = result.expect_err("prove must fail without a binary");
+    assert!(
+        matches!(
+            err.downcast_ref::<ProverError>(),
+            Some(ProverError::Unavailable(_))
+        ),
+        "unexpected error: {:#}",
+        err
+    );
+}
+
+#[test]
+#[serial]
+fn decode_spell_uses_charms_bin() {
+    env::set_var("CHARMS_BIN", "/nonexistent/charms");
+    let result = decode_spell_hex("00");
+    env::remove_var("CHARMS_BIN");
+
+    let err = result.expect_err("decode must fail without a binary");
+    assert!(
+        err.to_string().contains("CHARMS_BIN set to"),
+        "decode should resolve the binary through CHARMS_BIN. Got: {}",
+        err
+    );
+}
+
+#[test]
+#[serial]
+fn create_unsigned_with_mock_prover() {
+    let prover = MockProver::default();
+    let user_addr = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+    let funding_utxo = format!("{}:1", "11".repeat(32));
+    let habit_name = unique_habit_name("Mock Prover Test");
+
+    let unsigned = create_nft_unsigned(
+        &prover,
+        habit_name.clone(),
+        user_addr.to_string(),
+        funding_utxo,
+        10_000,
+        CreateOptions::default(),
+    )
+    .expect("create unsigned");
+
+    let spells = prover.spells.lock().unwrap();
+    assert_eq!(spells.len(), 1);
+    let charm = &spells[0]["outs"][0]["charms"]["$00"];
+    assert_eq!(charm["habit_name"], habit_name);
+    assert_eq!(charm["total_sessions"], 0);
+    assert_eq!(charm["owner"], user_addr);
+
+    assert_eq!(unsigned.spell_inputs_info.len(), 2);
+    assert_eq!(unsigned.spell_inputs_info[0].amount_sats, 10_000);
+    assert_eq!(
+        unsigned.spell_inputs_info[1].amount_sats,
+        10_000 - MockProver::FEE_SATS
+    );
+}
+
+#[test]
+fn create_unsigned_mints_to_destination() {
+    let prover = MockProver::default();
+    let user_addr = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+    let cold_addr = "bcrt1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqc8gma6";
+
+    create_nft_unsigned(
+        &prover,
+        unique_habit_name("Unsigned Destination Test"),
+        user_addr.to_string(),
+        format!("{}:0", "66".repeat(32)),
+        10_000,
+        CreateOptions {
+            to: Some(cold_addr.to_string()),
+            ..Default::default()
+        },
+    )
+    .expect("create unsigned");
+
+    let spell = prover.spells.lock().unwrap()[0].clone();
+    assert_eq!(spell["outs"][0]["address"], cold_addr);
+    assert_eq!(spell["outs"][0]["charms"]["$00"]["owner"], cold_addr);
+}
+
+#[test]
+fn session_store_bounds_size_and_expires() {
+    let store = SessionStore {
+        ttl: std::time::Duration::from_millis(200),
+        max_entries: 2,
+        entries: std::sync::Mutex::new(HashMap::new()),
+    };
+
+    let first = store.insert(&serde_json::json!({"n": 1})).expect("id");
+    let second = store.insert(&serde_json::json!({"n": 2})).expect("id");
+    let third = store.insert(&serde_json::json!({"n": 3})).expect("id");
+
+    assert!(store.get(&first).is_none(), "oldest evicted when full");
+    assert_eq!(store.get(&second).expect("second")["n"], 2);
+    assert_eq!(store.get(&third).expect("third")["n"], 3);
+
+    std::thread::sleep(std::time::Duration::from_millis(250));
+    assert!(store.get(&third).is_none(), "expired after ttl");
+
+    let disabled = SessionStore {
+        max_entries: 0,
+        ..store
+    };
+    assert!(disabled.insert(&serde_json::json!({})).is_none());
+}
+
+#[test]
+#[serial]
+fn underfunded_create_fails_before_proving() {
+    let prover = MockProver::default();
+    let err = create_nft_unsigned(
+        &prover,
+        unique_habit_name("Underfunded Test"),
+        "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080".to_string(),
+        format!("{}:0", "22".repeat(32)),
+        2100,
+        CreateOptions::default(),
+    )
+    .expect_err("2100 sats can't cover the NFT and fees");
+
+    assert!(err.to_string().contains("short by"), "got: {}", err);
+    assert!(
+        prover.spells.lock().unwrap().is_empty(),
+        "prover must not be called"
+    );
+}
+
+#[test]
+#[serial]
+fn inspect_flags_unsigned_and_reports_nft_output() {
+    let contract_path = get_contract_path();
+    assert!(
+        contract_path.exists(),
+        "Contract WASM required. Run: make contract"
+    );
+
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+    let user_addr = bitcoin.get_new_address().expect("get address");
+    let funding_utxo = bitcoin.get_first_utxo().expect("get funding utxo");
+
+    let unsigned = create_nft_unsigned(
+        &CliProver::default(),
+        unique_habit_name("Inspect Test"),
+        user_addr.to_string(),
+        format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
+        funding_utxo.amount.to_sat(),
+        CreateOptions::default(),
+    )
+    .expect("create unsigned");
+
+    // Unsigned commit: flagged, but its fee is known from the funding UTXO
+    let commit = inspect_signed_tx(&bitcoin.client, &unsigned.commit_tx_hex).expect("inspect");
+    assert!(commit.anomalies.iter().any(|a| a.contains("not signed")));
+    assert!(commit.fee.is_some());
+    assert!(!commit.nft_output_present);
+
+    let signed = sign_transactions(
+        &bitcoin.client,
+        &unsigned.commit_tx_hex,
+        &unsigned.spell_tx_hex,
+        None,
+    )
+    .expect("sign transactions");
+
+    // Signed spell: NFT output present, fee unknown until the commit is out
+    let spell = inspect_signed_tx(&bitcoin.client, &signed.spell_hex).expect("inspect");
+    assert!(spell.nft_output_present);
+    assert!(spell.anomalies.is_empty(), "{:?}", spell.anomalies);
+    assert_eq!(spell.fee, None);
+}
+
+#[test]
+#[serial]
+fn confirmation_detail_tracks_block_hash() {
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &MockProver::default(),
+        unique_habit_name("Confirmation Test"),
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
+
+    let pending = confirmation_detail(&bitcoin.client, &nft_txid).expect("detail");
+    assert_eq!(pending.confirmations, 0);
+    assert!(pending.block_hash.is_none());
+
+    bitcoin.mine_block().expect("mine block");
+
+    let confirmed = confirmation_detail(&bitcoin.client, &nft_txid).expect("detail");
+    let tip = bitcoin.client.get_best_block_hash().expect("tip");
+    let height = bitcoin.client.get_block_count().expect("height");
+    assert_eq!(confirmed.confirmations, 1);
+    assert_eq!(confirmed.block_hash, Some(tip.to_string()));
+    assert_eq!(confirmed.block_height, Some(height));
+}
+
+#[test]
+#[serial]
+fn create_many_fails_habits_independently() {
+    let prover = MockProver::default();
+    let user_addr = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+    let habits: Vec<String> = ["A", "B", "C"]
+        .iter()
+        .map(|h| unique_habit_name(h))
+        .collect();
+
+    // Not enough funding is rejected before anything is proved
+    let err = create_many_unsigned(
+        &prover,
+        habits.clone(),
+        user_addr,
+        vec![(format!("{}:0", "33".repeat(32)), 10_000)],
+    )
+    .expect_err("one UTXO can't fund three habits");
+    assert!(err.to_string().contains("funding UTXOs"), "got: {}", err);
+    assert!(prover.spells.lock().unwrap().is_empty());
+
+    // The underfunded middle habit fails on its own
+    let results = create_many_unsigned(
+        &prover,
+        habits.clone(),
+        user_addr,
+        vec![
+            (format!("{}:0", "33".repeat(32)), 10_000),
+            (format!("{}:1", "33".repeat(32)), 1_500),
+            (format!("{}:2", "33".repeat(32)), 10_000),
+        ],
+    )
+    .expect("batch");
+
+    assert_eq!(results.len(), 3);
+    assert!(results[0].unsigned.is_some());
+    assert!(results[1].error.is_some());
+    assert!(results[2].unsigned.is_some());
+    assert_eq!(results[1].habit, habits[1]);
+
+    // Each created habit gets its own identity
+    let spells = prover.spells.lock().unwrap();
+    assert_eq!(spells.len(), 2);
+    assert_ne!(spells[0]["apps"]["$00"], spells[1]["apps"]["$00"]);
+}
+
+#[test]
+fn p2sh_and_p2pkh_addresses_are_rejected() {
+    let script = bitcoin::ScriptBuf::from_bytes(vec![0x51]);
+    let p2sh = bitcoin::Address::p2sh(&script, bitcoin::Network::Regtest)
+        .expect("p2sh")
+        .to_string();
+    let p2pkh = "mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn";
+
+    for address in [p2sh.as_str(), p2pkh] {
+        let err = validate_address_type(address).expect_err("must reject");
+        assert!(
+            err.to_string().contains("Unsupported address type"),
+            "got: {}",
+            err
+        );
+    }
+
+    validate_address_type("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080").expect("p2wpkh");
+    validate_address_type("bcrt1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqc8gma6")
+        .expect("p2tr");
+
+    // Rejected before the prover is ever called
+    let prover = MockProver::default();
+    let err = create_nft_unsigned(
+        &prover,
+        unique_habit_name("P2SH Test"),
+        p2sh,
+        format!("{}:0", "44".repeat(32)),
+        10_000,
+        CreateOptions::default(),
+    )
+    .expect_err("p2sh owner");
+    assert!(err.to_string().contains("Unsupported address type"));
+    assert!(prover.spells.lock().unwrap().is_empty());
+}
+
+#[test]
+#[serial]
+fn fee_rate_outside_bounds_is_rejected() {
+    assert_eq!(check_fee_rate(2.0).expect("default bounds"), 2.0);
+
+    let err = check_fee_rate(200.0).expect_err("typo'd fee rate");
+    assert!(err.to_string().contains("200"), "got: {}", err);
+    assert!(check_fee_rate(0.5).is_err());
+
+    env::set_var("HABIT_FEE_RATE_MAX", "300");
+    let raised = check_fee_rate(200.0);
+    env::remove_var("HABIT_FEE_RATE_MAX");
+    assert!(raised.is_ok());
+}
+
+#[test]
+#[serial]
+fn predicted_nft_utxo_matches_broadcast() {
+    let contract_path = get_contract_path();
+    assert!(
+        contract_path.exists(),
+        "Contract WASM required. Run: make contract"
+    );
+
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+    let user_addr = bitcoin.get_new_address().expect("get address");
+    let funding_utxo = bitcoin.get_first_utxo().expect("get funding utxo");
+
+    let unsigned = create_nft_unsigned(
+        &CliProver::default(),
+        unique_habit_name("Predicted UTXO Test"),
+        user_addr.to_string(),
+        format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
+        funding_utxo.amount.to_sat(),
+        CreateOptions::default(),
+    )
+    .expect("create unsigned");
+    let signed = sign_transactions(
+        &bitcoin.client,
+        &unsigned.commit_tx_hex,
+        &unsigned.spell_tx_hex,
+        None,
+    )
+    .expect("sign transactions");
+    let created =
+        broadcast_nft(&bitcoin.client, signed.commit_hex, signed.spell_hex).expect("broadcast");
+    bitcoin.mine_block().expect("mine block");
+
+    assert_eq!(
+        unsigned.predicted_nft_utxo,
+        format!("{}:0", created.spell_txid)
+    );
+
+    // Same holds for updates, where the NFT input is signed too
+    let nft_utxo = bitcoin
+        .find_nft_by_txid(&created.spell_txid)
+        .expect("find NFT");
+    let funding_utxo = bitcoin.get_funding_utxo().expect("get funding");
+    let unsigned = update_nft_unsigned(
+        &bitcoin.client,
+        &CliProver::default(),
+        unsigned.predicted_nft_utxo,
+        user_addr.to_string(),
+        format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
+        funding_utxo.amount.to_sat(),
+        UpdateOptions::default(),
+    )
+    .expect("create unsigned update");
+    let signed = sign_transactions(
+        &bitcoin.client,
+        &unsigned.commit_tx_hex,
+        &unsigned.spell_tx_hex,
+        Some(&nft_utxo),
+    )
+    .expect("sign transactions");
+    let updated =
+        broadcast_nft(&bitcoin.client, signed.commit_hex, signed.spell_hex).expect("broadcast");
+
+    assert_eq!(
+        unsigned.predicted_nft_utxo,
+        format!("{}:0", updated.spell_txid)
+    );
+}
+
+#[test]
+fn partial_signatures_name_unsigned_inputs() {
+    let txid = Txid::from_str(&"55".repeat(32)).unwrap();
+    let partial = bitcoincore_rpc::json::SignRawTransactionResult {
+        hex: vec![],
+        complete: false,
+        errors: Some(vec![bitcoincore_rpc::json::SignRawTransactionResultError {
+            txid,
+            vout: 1,
+            script_sig: bitcoin::ScriptBuf::new(),
+            sequence: 0xffffffff,
+            error: "Unable to sign input, missing key".to_string(),
+        }]),
+    };
+
+    let err = ensure_signed("spell", &partial).expect_err("incomplete");
+    assert_eq!(
+        err.inputs,
+        vec![(
+            format!("{}:1", txid),
+            "Unable to sign input, missing key".to_string()
+        )]
+    );
+    assert!(err.to_string().contains(&format!("{}:1", txid)));
+
+    let complete = bitcoincore_rpc::json::SignRawTransactionResult {
+        complete: true,
+        errors: None,
+        ..partial
+    };
+    assert!(ensure_signed("spell", &complete).is_ok());
+}
+
+#[test]
+#[serial]
+fn consolidate_sweeps_funds_but_not_nfts() {
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &MockProver::default(),
+        unique_habit_name("Consolidate Test"),
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let result = consolidate_utxos(&bitcoin.client, Some(2.0)).expect("consolidate");
+    assert!(result.inputs >= 2);
+    bitcoin.mine_block().expect("mine block");
+
+    let utxos = bitcoin
+        .client
+        .list_unspent(None, None, None, None, None)
+        .expect("list unspent");
+    assert!(
+        utxos
+            .iter()
+            .any(|u| u.txid.to_string() == nft_txid && u.vout == 0),
+        "NFT must survive the sweep"
+    );
+    assert!(utxos
+        .iter()
+        .any(|u| format!("{}:{}", u.txid, u.vout) == result.utxo));
+}
+
+#[test]
+#[serial]
+fn funding_and_consolidate_skip_nfts_of_any_value() {
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    // Stand-in for an NFT minted by another tool at a different value
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &MintValueProver(1500, MockProver::default()),
+        unique_habit_name("Odd Value Test"),
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
+    bitcoin.mine_block().expect("mine block");
+    let nft_utxo = format!("{}:0", nft_txid);
+
+    // Mock spells can't be decoded, so hand the cache the charm `charms` would find
+    with_spell_cache(|cache| {
+        cache.put(
+            nft_txid.clone(),
+            serde_json::json!({"outs": [{"charms": {"$0000": {"habit_name": "Odd Value Test"}}}]}),
+        )
+    });
+
+    let others: Vec<String> = bitcoin
+        .client
+        .list_unspent(None, None, None, None, None)
+        .expect("list unspent")
+        .iter()
+        .map(|u| format!("{}:{}", u.txid, u.vout))
+        .filter(|utxo| *utxo != nft_utxo)
+        .collect();
+    let others: Vec<&str> = others.iter().map(String::as_str).collect();
+    let err = get_funding_utxo(&bitcoin.client, &others).expect_err("only the NFT is left");
+    assert!(err.to_string().contains("No funding UTXOs"), "got: {}", err);
+
+    consolidate_utxos(&bitcoin.client, Some(2.0)).expect("consolidate");
+    bitcoin.mine_block().expect("mine block");
+
+    let (txid, vout) = parse_utxo(&nft_utxo).unwrap();
+    assert!(
+        bitcoin
+            .client
+            .get_tx_out(&txid, vout, Some(true))
+            .expect("get tx out")
+            .is_some(),
+        "NFT must survive the sweep"
+    );
+}
+
+#[test]
+#[serial]
+fn contract_paths_honor_env_overrides() {
+    env::set_var("HABIT_CONTRACT_WASM", "/opt/habit/contract.wasm");
+    env::set_var("HABIT_CONTRACT_VK", "/opt/habit/contract.vk");
+    let wasm = get_contract_path();
+    let vk = get_contract_vk_path();
+    let missing = load_contract();
+    env::remove_var("HABIT_CONTRACT_WASM");
+    env::remove_var("HABIT_CONTRACT_VK");
+
+    assert_eq!(wasm, std::path::PathBuf::from("/opt/habit/contract.wasm"));
+    assert_eq!(vk, std::path::PathBuf::from("/opt/habit/contract.vk"));
+    let err = missing.expect_err("override points at nothing");
+    assert!(
+        err.to_string().contains("/opt/habit/contract.wasm"),
+        "got: {}",
+        err
+    );
+
+    assert!(get_contract_path().ends_with("contracts/habit-tracker.wasm"));
+}
+
+#[test]
+#[serial]
+fn update_rejects_stale_expected_sessions() {
+    let contract_path = get_contract_path();
+    assert!(
+        contract_path.exists(),
+        "Contract WASM required. Run: make contract"
+    );
+
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        unique_habit_name("Stale State Test"),
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let (_, _, owner) = extract_nft_metadata(&bitcoin.client, &nft_txid).expect("extract metadata");
+    let nft_utxo = bitcoin.find_nft_by_txid(&nft_txid).expect("find NFT");
+    let funding_utxo = bitcoin.get_funding_utxo().expect("get funding");
+
+    // First update brings the NFT to 1 session on chain
+    let unsigned = update_nft_unsigned(
+        &bitcoin.client,
+        &CliProver::default(),
+        format!("{}:0", nft_txid),
+        owner.clone(),
+        format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
+        funding_utxo.amount.to_sat(),
+        UpdateOptions {
+            expected_sessions: Some(0),
+            ..Default::default()
+        },
+    )
+    .expect("create unsigned update");
+    let signed = sign_transactions(
+        &bitcoin.client,
+        &unsigned.commit_tx_hex,
+        &unsigned.spell_tx_hex,
+        Some(&nft_utxo),
+    )
+    .expect("sign transactions");
+    let broadcast =
+        broadcast_nft(&bitcoin.client, signed.commit_hex, signed.spell_hex).expect("broadcast");
+    bitcoin.mine_block().expect("mine block");
+
+    // A client still believing the NFT has 0 sessions is refused
+    let funding_utxo = bitcoin.get_funding_utxo().expect("get funding");
+    let err = update_nft_unsigned(
+        &bitcoin.client,
+        &CliProver::default(),
+        format!("{}:0", broadcast.spell_txid),
+        owner,
+        format!("{}:{}", funding_utxo.txid, funding_utxo.vout),
+        funding_utxo.amount.to_sat(),
+        UpdateOptions {
+            expected_sessions: Some(0),
+            ..Default::default()
+        },
+    )
+    .expect_err("stale expected_sessions");
+    assert!(err.to_string().contains("Stale update"), "got: {}", err);
+}
+
+#[test]
+fn compute_app_id_hashes_seed() {
+    assert_eq!(
+        compute_app_id("abc", "vk123"),
+        "n/ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad/vk123"
+    );
+    assert_eq!(vk_from_app_id(&compute_app_id("seed", "vk123")), "vk123");
+}
+
+#[test]
+fn missing_tx_errors_are_recognized() {
+    let missing = bitcoincore_rpc::Error::JsonRpc(bitcoincore_rpc::jsonrpc::Error::Rpc(
+        bitcoincore_rpc::jsonrpc::error::RpcError {
+            code: -5,
+            message: "No such mempool or blockchain transaction. Use gettransaction for wallet transactions.".to_string(),
+            data: None,
+        },
+    ));
+    assert!(is_missing_tx_error(&missing));
+
+    let other = bitcoincore_rpc::Error::ReturnedError("Work queue depth exceeded".to_string());
+    assert!(!is_missing_tx_error(&other));
+}
+
+#[test]
+fn rate_limiter_throttles_per_ip() {
+    let limiter = RateLimiter::new(2);
+    let client: std::net::IpAddr = "10.0.0.1".parse().unwrap();
+    let other: std::net::IpAddr = "10.0.0.2".parse().unwrap();
+
+    assert!(limiter.check(client).is_ok());
+    assert!(limiter.check(client).is_ok());
+    let wait = limiter
+        .check(client)
+        .expect_err("third request in a minute");
+    assert!(wait.as_secs_f64() > 0.0 && wait.as_secs() <= 30);
+
+    assert!(limiter.check(other).is_ok(), "buckets are per IP");
+    assert!(RateLimiter::new(0).check(client).is_ok(), "0 disables");
+}
+
+#[test]
+fn create_metadata_lands_in_meta_and_is_capped() {
+    let prover = MockProver::default();
+    let user_addr = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+    let meta = HashMap::from([("user_id".to_string(), "42".to_string())]);
+
+    create_nft_unsigned(
+        &prover,
+        unique_habit_name("Meta Test"),
+        user_addr.to_string(),
+        format!("{}:0", "55".repeat(32)),
+        10_000,
+        CreateOptions {
+            meta: Some(meta),
+            ..Default::default()
+        },
+    )
+    .expect("create unsigned");
+
+    let charm = prover.spells.lock().unwrap()[0]["outs"][0]["charms"]["$00"].clone();
+    assert_eq!(charm["meta"]["user_id"], "42");
+    assert_eq!(charm["total_sessions"], 0, "meta doesn't displace fields");
+
+    let oversized = HashMap::from([("blob".to_string(), "x".repeat(MAX_META_BYTES))]);
+    let err = create_nft_unsigned(
+        &prover,
+        unique_habit_name("Meta Test"),
+        user_addr.to_string(),
+        format!("{}:1", "55".repeat(32)),
+        10_000,
+        CreateOptions {
+            meta: Some(oversized),
+            ..Default::default()
+        },
+    )
+    .expect_err("meta over the cap");
+    assert!(err.to_string().contains("limit"), "got: {}", err);
+    assert_eq!(
+        prover.spells.lock().unwrap().len(),
+        1,
+        "rejected before proving"
+    );
+}
+
+#[test]
+fn non_string_meta_is_rejected() {
+    // Served from the spell cache, so the node is never contacted
+    let btc = BitcoinCoreClient::new("http://127.0.0.1:1", Auth::None).unwrap();
+    let spell_with_meta = |meta: serde_json::Value| {
+        serde_json::json!({
+            "apps": {"$00": "n/seed/vk"},
+            "outs": [{"charms": {"$0000": {
+                "habit_name": "Reading",
+                "total_sessions": 0,
+                "owner": "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080",
+                "meta": meta,
+            }}}]
+        })
+    };
+    let fetch = |txid: String, meta: serde_json::Value| {
+        with_spell_cache(|cache| cache.put(txid.clone(), spell_with_meta(meta)));
+        fetch_nft_metadata(&btc, &txid)
+    };
+
+    let metadata = fetch("a1".repeat(32), serde_json::json!({"source": "app"})).expect("meta");
+    assert_eq!(metadata.meta.unwrap()["source"], "app");
+
+    let err = fetch("a2".repeat(32), serde_json::json!({"streak": 3})).expect_err("number");
+    assert!(err.to_string().contains("streak"), "got: {}", err);
+}
+
+/// Serve one canned HTTP response per connection, returning the requests
+/// received (headers and body) once all responses are sent
+fn fake_prover_server(responses: Vec<String>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        responses
+            .into_iter()
+            .map(|response| {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 8192];
+                loop {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).to_lowercase();
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let len = text
+                            .lines()
+                            .find_map(|l| l.strip_prefix("content-length:"))
+                            .map_or(0, |v| v.trim().parse().unwrap());
+                        if request.len() >= end + 4 + len {
+                            break;
+                        }
+                    }
+                }
+                stream.write_all(response.as_bytes()).unwrap();
+                String::from_utf8_lossy(&request).into_owned()
+            })
+            .collect()
+    });
+    (url, server)
+}
+
+fn http_response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+#[test]
+fn http_prover_sends_token_and_reports_auth_failures() {
+    let (url, server) = fake_prover_server(vec![http_response("401 Unauthorized", "bad")]);
+
+    let err = prove_with_http(
+        &url,
+        Some("secret"),
+        &serde_json::json!({"version": SPELL_VERSION}),
+        "vk",
+        "AA==",
+        &[],
+        &format!("{}:0", "66".repeat(32)),
+        10_000,
+        "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080",
+        2.0,
+    )
+    .expect_err("401 from prover");
+
+    let request = server.join().unwrap().remove(0);
+    assert!(
+        request.starts_with("POST /spells/prove"),
+        "got: {}",
+        request
+    );
+    assert!(
+        request
+            .to_lowercase()
+            .contains("authorization: bearer secret"),
+        "token not sent: {}",
+        request
+    );
+    assert!(
+        matches!(
+            err.downcast_ref::<ProverError>(),
+            Some(ProverError::Unauthorized(_))
+        ),
+        "got: {:#}",
+        err
+    );
+}
+
+#[test]
+fn http_prover_uploads_binary_only_when_asked() {
+    let user_addr = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+    let funding_utxo = format!("{}:0", "88".repeat(32));
+    let txs = MockProver::default()
+        .prove(
+            &serde_json::json!({"outs": [{"address": user_addr}]}),
+            &[],
+            &funding_utxo,
+            10_000,
+            user_addr,
+            2.0,
+        )
+        .expect("mock prove");
+
+    let (url, server) = fake_prover_server(vec![
+        http_response(
+            "400 Bad Request",
+            "\"missing app binaries for vks: [vkvkvk]\"",
+        ),
+        http_response("200 OK", &serde_json::to_string(&txs).unwrap()),
+    ]);
+
+    let proved = prove_with_http(
+        &url,
+        None,
+        &serde_json::json!({"version": SPELL_VERSION}),
+        "vkvkvk",
+        "V0FTTUJJTkFSWQ==",
+        &[],
+        &funding_utxo,
+        10_000,
+        user_addr,
+        2.0,
+    )
+    .expect("prove after upload");
+    assert_eq!(proved, txs);
+
+    let requests = server.join().unwrap();
+    assert!(
+        !requests[0].contains("V0FTTUJJTkFSWQ=="),
+        "first attempt is VK-only"
+    );
+    assert!(
+        requests[1].contains("V0FTTUJJTkFSWQ=="),
+        "binary sent once the prover asks"
+    );
+
+    // Any other rejection, even one naming the VK, is passed on as is
+    let (url, server) = fake_prover_server(vec![http_response(
+        "400 Bad Request",
+        "\"app contract vkvkvk: binary check failed\"",
+    )]);
+    let err = prove_with_http(
+        &url,
+        None,
+        &serde_json::json!({"version": SPELL_VERSION}),
+        "vkvkvk",
+        "V0FTTUJJTkFSWQ==",
+        &[],
+        &funding_utxo,
+        10_000,
+        user_addr,
+        2.0,
+    )
+    .expect_err("rejection");
+    assert!(matches!(
+        err.downcast_ref::<ProverError>(),
+        Some(ProverError::Rejected(_))
+    ));
+    assert_eq!(server.join().unwrap().len(), 1, "no upload retry");
+}
+
+#[test]
+#[serial]
+fn offline_signing_round_trip_through_files() {
+    let contract_path = get_contract_path();
+    assert!(
+        contract_path.exists(),
+        "Contract WASM required. Run: make contract"
+    );
+
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+    let dir = tempfile::tempdir().expect("temp dir");
+    let unsigned_path = dir.path().join("unsigned.json");
+
+    export_create_unsigned(
+        &bitcoin.client,
+        &CliProver::default(),
+        unique_habit_name("Offline Signing Test"),
+        &unsigned_path,
+    )
+    .expect("export unsigned create");
+
+    // What the offline machine receives
+    let unsigned: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&unsigned_path).unwrap()).unwrap();
+    let signed = sign_transactions(
+        &bitcoin.client,
+        unsigned["commit_tx_hex"].as_str().unwrap(),
+        unsigned["spell_tx_hex"].as_str().unwrap(),
+        None,
+    )
+    .expect("sign transactions");
+
+    let commit_path = dir.path().join("commit.hex");
+    let spell_path = dir.path().join("spell.hex");
+    std::fs::write(&commit_path, format!("{}\n", signed.commit_hex)).unwrap();
+    std::fs::write(&spell_path, format!("{}\n", signed.spell_hex)).unwrap();
+
+    broadcast_from_files(&bitcoin.client, &commit_path, &spell_path).expect("broadcast");
+    bitcoin.mine_block().expect("mine block");
+
+    let predicted = unsigned["predicted_nft_utxo"].as_str().unwrap();
+    let (txid, _) = predicted.split_once(':').unwrap();
+    let metadata = fetch_nft_metadata(&bitcoin.client, txid).expect("NFT on chain");
+    assert_eq!(metadata.total_sessions, 0);
+}
+
+#[test]
+fn classify_txs_ignores_prover_ordering() {
+    let user_addr = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+    let txs = MockProver::default()
+        .prove(
+            &serde_json::json!({"outs": [{"address": user_addr}]}),
+            &[],
+            &format!("{}:0", "77".repeat(32)),
+            10_000,
+            user_addr,
+            2.0,
+        )
+        .expect("mock prove");
+
+    let (commit, spell) = classify_txs(&txs).expect("in order");
+    assert_eq!(spell.input[0].previous_output.txid, commit.compute_txid());
+
+    let reversed: Vec<_> = txs.iter().rev().cloned().collect();
+    let (r_commit, r_spell) = classify_txs(&reversed).expect("reversed");
+    assert_eq!(r_commit, commit);
+    assert_eq!(r_spell, spell);
+
+    let err = classify_txs(&txs[..1]).expect_err("no spell tx");
+    assert!(err.to_string().contains("commit tx"), "got: {}", err);
+}
+
+#[test]
+#[serial]
+fn lifetime_cost_walks_back_to_mint() {
+    let contract_path = get_contract_path();
+    assert!(
+        contract_path.exists(),
+        "Contract WASM required. Run: make contract"
+    );
+
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        unique_habit_name("Cost Test"),
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let minted = lifetime_cost(&bitcoin.client, &format!("{}:0", nft_txid)).expect("mint cost");
+    assert_eq!(minted.total_sessions, 0);
+    assert_eq!(minted.transactions, 2, "commit + spell");
+    assert!(minted.total_fees_sats > 0);
+
+    update_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        format!("{}:0", nft_txid),
+        UpdateOptions::default(),
+    )
+    .expect("update NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let updated_nft = bitcoin.find_nft_utxo().expect("find updated NFT");
+    let cost = lifetime_cost(
+        &bitcoin.client,
+        &format!("{}:{}", updated_nft.txid, updated_nft.vout),
+    )
+    .expect("lifetime cost");
+
+    assert_eq!(cost.total_sessions, 1);
+    assert_eq!(cost.transactions, 4);
+    assert_eq!(cost.spells, 2);
+    assert!(cost.total_fees_sats > minted.total_fees_sats);
+    assert_eq!(cost.avg_fee_per_session, cost.total_fees_sats as f64 / 2.0);
+}
+
+#[test]
+#[serial]
+fn lifetime_cost_counts_rename_spells() {
+    let contract_path = get_contract_path();
+    assert!(
+        contract_path.exists(),
+        "Contract WASM required. Run: make contract"
+    );
+
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        unique_habit_name("Cost Rename Test"),
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    rename_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        format!("{}:0", nft_txid),
+        unique_habit_name("Cost Renamed"),
+    )
+    .expect("rename NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let renamed_nft = bitcoin.find_nft_utxo().expect("find renamed NFT");
+    let cost = lifetime_cost(
+        &bitcoin.client,
+        &format!("{}:{}", renamed_nft.txid, renamed_nft.vout),
+    )
+    .expect("lifetime cost");
+
+    // A rename adds a spell but no session
+    assert_eq!(cost.total_sessions, 0);
+    assert_eq!(cost.spells, 2);
+    assert_eq!(cost.transactions, 4);
+    assert_eq!(cost.avg_fee_per_session, cost.total_fees_sats as f64 / 2.0);
+}
+
+#[test]
+#[serial]
+fn funding_skips_utxos_spent_in_mempool() {
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let (first, _, _) = get_funding_utxo(&bitcoin.client, &[]).expect("funding");
+
+    // Spent without mining, as a concurrent update would; the node stops
+    // listing it even while unconfirmed
+    spend_in_mempool(&bitcoin, &first);
+
+    let (next, _, _) = get_funding_utxo(&bitcoin.client, &[]).expect("funding");
+    assert_ne!(next, first, "UTXO spent in the mempool must be skipped");
+}
+
+/// Spend a wallet UTXO without mining the spend
+fn spend_in_mempool(bitcoin: &TestBitcoin, utxo: &str) {
+    let (txid, vout) = parse_utxo(utxo).unwrap();
+    let address = bitcoin.get_new_address().expect("address");
+    let raw = bitcoin
+        .client
+        .create_raw_transaction(
+            &[bitcoincore_rpc::json::CreateRawTransactionInput {
+                txid,
+                vout,
+                sequence: None,
+            }],
+            &HashMap::from([(address.to_string(), bitcoin::Amount::from_sat(5_000))]),
+            None,
+            None,
+        )
+        .expect("create raw tx");
+    let funded = bitcoin
+        .client
+        .fund_raw_transaction(&raw, None, None)
+        .expect("fund raw tx");
+    let signed = bitcoin
+        .client
+        .sign_raw_transaction_with_wallet(&funded.hex, None, None)
+        .expect("sign raw tx");
+    bitcoin
+        .client
+        .send_raw_transaction(&signed.hex)
+        .expect("send raw tx");
+}
+
+/// Fails its first proof with a missing-inputs rejection, optionally after
+/// spending the funding UTXO it was given as a concurrent invocation would
+struct FlakyProver<'a> {
+    bitcoin: &'a TestBitcoin,
+    spend_funding: bool,
+    funding_seen: std::sync::Mutex<Vec<String>>,
+    inner: MockProver,
+}
+
+impl Prover for FlakyProver<'_> {
+    fn prove(
+        &self,
+        spell: &serde_json::Value,
+        prev_txs: &[String],
+        funding_utxo: &str,
+        funding_utxo_value: u64,
+        change_address: &str,
+        fee_rate: f64,
+    ) -> anyhow::Result<Vec<charms_client::tx::Tx>> {
+        let mut seen = self.funding_seen.lock().unwrap();
+        seen.push(funding_utxo.to_string());
+        if seen.len() == 1 {
+            if self.spend_funding {
+                spend_in_mempool(self.bitcoin, funding_utxo);
+            }
+            anyhow::bail!("bad-txns-inputs-missingorspent");
+        }
+        self.inner.prove(
+            spell,
+            prev_txs,
+            funding_utxo,
+            funding_utxo_value,
+            change_address,
+            fee_rate,
+        )
+    }
+
+    fn vk(&self) -> anyhow::Result<String> {
+        self.inner.vk()
+    }
+}
+
+#[test]
+#[serial]
+fn funding_retry_only_when_the_funding_utxo_is_gone() {
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let flaky = |spend_funding| FlakyProver {
+        bitcoin: &bitcoin,
+        spend_funding,
+        funding_seen: Default::default(),
+        inner: MockProver::default(),
+    };
+
+    // Funding taken by someone else: retried with another UTXO
+    let prover = flaky(true);
+    create_nft(
+        &bitcoin.client,
+        &prover,
+        unique_habit_name("Retry Test"),
+        CreateOptions::default(),
+    )
+    .expect("retry should succeed");
+    let seen = prover.funding_seen.lock().unwrap().clone();
+    assert_eq!(seen.len(), 2);
+    assert_ne!(seen[0], seen[1]);
+
+    // Funding still unspent, so some other input is missing: no retry
+    let prover = flaky(false);
+    let result = create_nft(
+        &bitcoin.client,
+        &prover,
+        unique_habit_name("No Retry Test"),
+        CreateOptions::default(),
+    );
+    assert!(result.is_err());
+    assert_eq!(prover.funding_seen.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn create_request_validation_names_the_field() {
+    let request = |address: &str, funding_utxo: &str, funding_value: u64| CreateNftRequest {
+        habit: "Meditation".to_string(),
+        address: address.to_string(),
+        funding_utxo: funding_utxo.to_string(),
+        funding_value,
+        metadata: None,
+    };
+    let addr = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+    let utxo = format!("{}:0", "99".repeat(32));
+
+    assert!(request(addr, &utxo, 10_000).validate().is_ok());
+
+    for (req, field) in [
+        (request("", &utxo, 10_000), "address"),
+        (request("not-an-address", &utxo, 10_000), "address"),
+        (request(addr, "abc", 10_000), "funding_utxo"),
+        (request(addr, &utxo, 0), "funding_value"),
+        (request(addr, &utxo, u64::MAX), "funding_value"),
+    ] {
+        match req.validate() {
+            Err(ApiError::Status(status, msg)) => {
+                assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+                assert!(msg.starts_with(field), "expected {}: {}", field, msg);
+            }
+            _ => panic!("expected 400 for {}", field),
+        }
+    }
+}
+
+#[test]
+fn extra_outputs_below_dust_are_rejected() {
+    let p2wpkh = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+    let p2tr = "bcrt1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqc8gma6";
+    let request = |address: &str, sats: u64| UpdateNftRequest {
+        nft_utxo: format!("{}:0", "88".repeat(32)),
+        user_address: p2wpkh.to_string(),
+        funding_utxo: format!("{}:1", "99".repeat(32)),
+        funding_value: 10_000,
+        extra_outputs: vec![ExtraOutput {
+            address: address.to_string(),
+            sats,
+        }],
+        expected_sessions: None,
+    };
+
+    assert_eq!(dust_limit(p2wpkh).unwrap(), 294);
+    assert_eq!(dust_limit(p2tr).unwrap(), 330);
+
+    assert!(request(p2wpkh, 294).validate().is_ok());
+    assert!(request(p2tr, 330).validate().is_ok());
+    for (address, sats) in [(p2wpkh, 0), (p2wpkh, 293), (p2tr, 329)] {
+        match request(address, sats).validate() {
+            Err(ApiError::Status(status, msg)) => {
+                assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+                assert!(msg.starts_with("extra_outputs[0].sats"), "{}", msg);
+            }
+            _ => panic!("{} sats to {} should be dust", sats, address),
+        }
+    }
+}
+
+#[test]
+#[serial]
+fn view_reports_pending_update_from_mempool() {
+    let contract_path = get_contract_path();
+    assert!(
+        contract_path.exists(),
+        "Contract WASM required. Run: make contract"
+    );
+
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        unique_habit_name("Pending Test"),
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let nft_utxo = format!("{}:0", nft_txid);
+    let (confirmed, pending_utxo) = fetch_nft_state(&bitcoin.client, &nft_utxo).expect("state");
+    assert_eq!(confirmed.total_sessions, 0);
+    assert!(pending_utxo.is_none());
+
+    // Broadcast an update but leave it in the mempool
+    update_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        nft_utxo.clone(),
+        UpdateOptions::default(),
+    )
+    .expect("update NFT");
+
+    let (pending, pending_utxo) = fetch_nft_state(&bitcoin.client, &nft_utxo).expect("state");
+    let pending_utxo = pending_utxo.expect("update should be pending");
+    assert_eq!(pending.total_sessions, 1);
+    assert_eq!(pending.habit_name, confirmed.habit_name);
+    assert_ne!(pending_utxo, nft_utxo);
+
+    bitcoin.mine_block().expect("mine block");
+
+    let (settled, still_pending) = fetch_nft_state(&bitcoin.client, &pending_utxo).expect("state");
+    assert_eq!(settled.total_sessions, 1);
+    assert!(still_pending.is_none());
+}
+
+#[test]
+#[serial]
+fn create_mints_to_a_separate_destination() {
+    let contract_path = get_contract_path();
+    assert!(
+        contract_path.exists(),
+        "Contract WASM required. Run: make contract"
+    );
+
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    // Mainnet destination on a regtest node
+    let err = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        unique_habit_name("Cold Test"),
+        CreateOptions {
+            to: Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string()),
+            ..Default::default()
+        },
+    )
+    .expect_err("wrong network must be rejected");
+    assert!(
+        err.to_string().contains("not a regtest address"),
+        "got: {}",
+        err
+    );
+
+    let cold = bitcoin.get_new_address().expect("address").to_string();
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        unique_habit_name("Cold Test"),
+        CreateOptions {
+            to: Some(cold.clone()),
+            ..Default::default()
+        },
+    )
+    .expect("create NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let metadata = fetch_nft_metadata(&bitcoin.client, &nft_txid).expect("metadata");
+    assert_eq!(metadata.owner, cold);
+
+    let spell_tx = bitcoin
+        .client
+        .get_raw_transaction(&nft_txid.parse().unwrap(), None)
+        .expect("spell tx");
+    assert_eq!(
+        spell_tx.output[0].script_pubkey,
+        bitcoin::Address::from_str(&cold)
+            .unwrap()
+            .assume_checked()
+            .script_pubkey()
+    );
+}
+
+/// Proves with the real contract but mints the NFT at a given value
+struct MintValueProver<P>(u64, P);
+
+impl<P: Prover> Prover for MintValueProver<P> {
+    fn prove(
+        &self,
+        spell: &serde_json::Value,
+        prev_txs: &[String],
+        funding_utxo: &str,
+        funding_utxo_value: u64,
+        change_address: &str,
+        fee_rate: f64,
+    ) -> anyhow::Result<Vec<charms_client::tx::Tx>> {
+        let mut spell = spell.clone();
+        spell["outs"][0]["sats"] = serde_json::json!(self.0);
+        self.1.prove(
+            &spell,
+            prev_txs,
+            funding_utxo,
+            funding_utxo_value,
+            change_address,
+            fee_rate,
+        )
+    }
+
+    fn vk(&self) -> anyhow::Result<String> {
+        self.1.vk()
+    }
+}
+
+#[test]
+#[serial]
+fn update_keeps_a_non_default_nft_value() {
+    let contract_path = get_contract_path();
+    assert!(
+        contract_path.exists(),
+        "Contract WASM required. Run: make contract"
+    );
+
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    // Stand-in for an NFT minted by another tool at a different value
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &MintValueProver(1500, CliProver::default()),
+        unique_habit_name("Custom Sats Test"),
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let nft_utxo = format!("{}:0", nft_txid);
+    let (txid, vout) = parse_utxo(&nft_utxo).unwrap();
+    let minted = bitcoin
+        .client
+        .get_tx_out(&txid, vout, Some(false))
+        .expect("gettxout")
+        .expect("NFT output");
+    assert_eq!(minted.value.to_sat(), 1500);
+
+    // Signing with an assumed 1000 sat prevout would fail here
+    update_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        nft_utxo,
+        UpdateOptions::default(),
+    )
+    .expect("update NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let updated = bitcoin
+        .client
+        .list_unspent(None, None, None, None, None)
+        .expect("listunspent")
+        .into_iter()
+        .find(|u| u.amount.to_sat() == 1500 && u.txid != txid)
+        .expect("updated NFT keeps its 1500 sats");
+    let metadata =
+        fetch_nft_metadata(&bitcoin.client, &updated.txid.to_string()).expect("metadata");
+    assert_eq!(metadata.total_sessions, 1);
+}
+
+#[tokio::test]
+async fn progress_streams_while_the_operation_runs() {
+    let mut seen = Vec::new();
+    let value = run_with_progress(
+        |progress| {
+            for stage in [
+                Progress::ProvingStarted,
+                Progress::ProvingDone,
+                Progress::Signing,
+                Progress::Broadcasting,
+            ] {
+                progress.send(stage)?;
+            }
+            Ok(42)
+        },
+        |stage| seen.push(stage),
+    )
+    .await
+    .expect("operation");
+
+    assert_eq!(value, 42);
+    assert_eq!(
+        seen,
+        [
+            Progress::ProvingStarted,
+            Progress::ProvingDone,
+            Progress::Signing,
+            Progress::Broadcasting,
+        ]
+    );
+
+    // Events sent before a failure still arrive, and the error comes through
+    let mut seen = Vec::new();
+    let err = run_with_progress(
+        |progress| -> anyhow::Result<()> {
+            progress.send(Progress::ProvingStarted)?;
+            anyhow::bail!("prover rejected the spell")
+        },
+        |stage| seen.push(stage),
+    )
+    .await
+    .expect_err("must fail");
+    assert_eq!(seen, [Progress::ProvingStarted]);
+    assert!(err.to_string().contains("prover rejected"));
+}
+
+/// Every stage an operation reported, once it has finished and dropped its sender
+fn drain_progress(mut rx: tokio::sync::mpsc::UnboundedReceiver<Progress>) -> Vec<Progress> {
+    std::iter::from_fn(|| rx.try_recv().ok()).collect()
+}
+
+#[test]
+#[serial]
+fn create_nft_reports_each_stage() {
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    create_nft(
+        &bitcoin.client,
+        &ReportingProver {
+            inner: &MockProver::default(),
+            progress: &tx,
+        },
+        unique_habit_name("Progress Test"),
+        CreateOptions {
+            progress: Some(tx.clone()),
+            ..Default::default()
+        },
+    )
+    .expect("create NFT");
+    drop(tx);
+
+    assert_eq!(
+        drain_progress(rx),
+        [
+            Progress::ProvingStarted,
+            Progress::ProvingDone,
+            Progress::Signing,
+            Progress::Broadcasting,
+        ]
+    );
+}
+
+#[test]
+fn reporting_prover_reports_proving_stages() {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    create_nft_unsigned(
+        &ReportingProver {
+            inner: &MockProver::default(),
+            progress: &tx,
+        },
+        unique_habit_name("Unsigned Progress Test"),
+        "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080".to_string(),
+        format!("{}:0", "44".repeat(32)),
+        10_000,
+        CreateOptions::default(),
+    )
+    .expect("create unsigned");
+    drop(tx);
+
+    assert_eq!(
+        drain_progress(rx),
+        [Progress::ProvingStarted, Progress::ProvingDone]
+    );
+}
+
+#[test]
+#[serial]
+fn wallet_ownership_is_checked_before_signing() {
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let (funding, _, change) = get_funding_utxo(&bitcoin.client, &[]).expect("funding");
+    ensure_wallet_owns_utxo(&bitcoin.client, &funding, "Funding").expect("wallet UTXO");
+    ensure_wallet_owns_address(&bitcoin.client, &change, "Change").expect("wallet address");
+
+    // Pay an address this wallet has no keys for
+    let foreign = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+    let err = ensure_wallet_owns_address(&bitcoin.client, foreign, "Change")
+        .expect_err("foreign address must be rejected");
+    assert!(
+        err.to_string().contains("not controlled by wallet"),
+        "got: {}",
+        err
+    );
+
+    let txid = bitcoin
+        .client
+        .send_to_address(
+            &bitcoin::Address::from_str(foreign)
+                .unwrap()
+                .assume_checked(),
+            bitcoin::Amount::from_sat(10_000),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .expect("send to foreign address");
+    let tx = bitcoin.client.get_raw_transaction(&txid, None).expect("tx");
+    let vout = tx
+        .output
+        .iter()
+        .position(|out| out.value.to_sat() == 10_000)
+        .expect("foreign output");
+
+    let err = ensure_wallet_owns_utxo(&bitcoin.client, &format!("{}:{}", txid, vout), "Funding")
+        .expect_err("foreign UTXO must be rejected");
+    assert!(err.to_string().contains("Funding UTXO"), "got: {}", err);
+    assert!(
+        err.to_string().contains("not controlled by wallet"),
+        "got: {}",
+        err
+    );
+}
+
+#[test]
+#[serial]
+fn spell_cache_skips_charms_until_the_nft_moves() {
+    let contract_path = get_contract_path();
+    assert!(
+        contract_path.exists(),
+        "Contract WASM required. Run: make contract"
+    );
+
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        unique_habit_name("Cache Test"),
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let is_cached = || with_spell_cache(|cache| cache.contains(&nft_txid)) == Some(true);
+
+    let first = fetch_nft_metadata(&bitcoin.client, &nft_txid).expect("metadata");
+    assert!(is_cached(), "decoded spell is cached");
+
+    let cached = fetch_nft_metadata(&bitcoin.client, &nft_txid).expect("metadata");
+    assert_eq!(cached.total_sessions, first.total_sessions);
+
+    // Spending the NFT evicts its spell
+    let spender = bitcoin::Transaction {
+        version: bitcoin::transaction::Version::TWO,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: vec![bitcoin::TxIn {
+            previous_output: bitcoin::OutPoint {
+                txid: nft_txid.parse().unwrap(),
+                vout: 0,
+            },
+            ..Default::default()
+        }],
+        output: vec![],
+    };
+    forget_spent_spells(&spender);
+    assert!(!is_cached(), "spent NFT's spell is evicted");
+
+    fetch_nft_metadata(&bitcoin.client, &nft_txid).expect("decoded again");
+    assert!(is_cached());
+}
+
+#[test]
+fn rename_request_validation_names_the_field() {
+    let request = |new_name: &str, nft_utxo: &str| RenameNftRequest {
+        nft_utxo: nft_utxo.to_string(),
+        new_name: new_name.to_string(),
+        user_address: "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080".to_string(),
+        funding_utxo: format!("{}:1", "99".repeat(32)),
+        funding_value: 10_000,
+    };
+    let utxo = format!("{}:0", "88".repeat(32));
+
+    assert!(request("Morning Meditation", &utxo).validate().is_ok());
+
+    let too_long = "x".repeat(MAX_HABIT_NAME_CHARS + 1);
+    for (req, field) in [
+        (request("   ", &utxo), "new_name"),
+        (request(&too_long, &utxo), "new_name"),
+        (request("Morning Meditation", "nope"), "nft_utxo"),
+    ] {
+        match req.validate() {
+            Err(ApiError::Status(status, msg)) => {
+                assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+                assert!(msg.starts_with(field), "expected {}: {}", field, msg);
+            }
+            _ => panic!("expected 400 for {}", field),
+        }
+    }
+}
+
+#[test]
+#[serial]
+fn rename_keeps_sessions() {
+    let contract_path = get_contract_path();
+    assert!(
+        contract_path.exists(),
+        "Contract WASM required. Run: make contract"
+    );
+
+    let bitcoin = setup_test_bitcoin().expect("setup bitcoin");
+
+    let nft_txid = create_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        unique_habit_name("Rename Test"),
+        CreateOptions::default(),
+    )
+    .expect("create NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    update_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        format!("{}:0", nft_txid),
+        UpdateOptions::default(),
+    )
+    .expect("update NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let updated = bitcoin.find_nft_utxo().expect("find updated NFT");
+    let nft_utxo = format!("{}:{}", updated.txid, updated.vout);
+    let before = fetch_nft_metadata(&bitcoin.client, &updated.txid.to_string()).expect("metadata");
+
+    let err = rename_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        nft_utxo.clone(),
+        before.habit_name.clone(),
+    )
+    .expect_err("same name must be rejected");
+    assert!(err.to_string().contains("already named"), "got: {}", err);
+
+    let new_name = unique_habit_name("Renamed");
+    rename_nft(
+        &bitcoin.client,
+        &CliProver::default(),
+        nft_utxo,
+        new_name.clone(),
+    )
+    .expect("rename NFT");
+    bitcoin.mine_block().expect("mine block");
+
+    let renamed = bitcoin.find_nft_utxo().expect("find renamed NFT");
+    let after = fetch_nft_metadata(&bitcoin.client, &renamed.txid.to_string()).expect("metadata");
+    assert_eq!(after.habit_name, new_name);
+    assert_eq!(after.total_sessions, before.total_sessions);
+    assert_eq!(after.app_id, before.app_id);
+}